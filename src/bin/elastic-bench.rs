@@ -0,0 +1,734 @@
+//! Run a workload against `ElasticHashMap` and baseline maps.
+//!
+//! ```text
+//! cargo run --release --bin elastic-bench -- --slots 1048576 --delta 0.01 \
+//!     --workload zipf --skew 0.99 --mix read-heavy --format csv
+//! ```
+//!
+//! Every map starts empty, is filled with `--prefill` keys and then runs
+//! `--ops` operations of the mix; only those are timed. Probes per
+//! operation are counted in a second, untimed run of the same operations
+//! with probe statistics enabled. Flags left out take the defaults of
+//! `ElasticHashMapBuilder`. Run `elastic-bench --help` for the flags.
+
+use std::{
+    collections::HashMap,
+    fmt::Write as _,
+    hash::{BuildHasher, Hash, RandomState},
+    hint::black_box,
+    io::Write as _,
+    process::ExitCode,
+    time::Instant,
+};
+
+use hashing::{
+    bucket::{
+        CollidingKeys, DefaultHashBuilder, ElasticHashMap, ElasticHashMapBuilder, HasherSeed, Keys,
+        NoHashCache, Op, OpMix, Operations, ProbeStats, SeededState, StringKeys, TraceTarget,
+    },
+    probe::{Prng, ProbeStrategy},
+};
+
+const USAGE: &str = "\
+Usage: elastic-bench [OPTIONS]
+
+Table:
+  --slots N             slots of the elastic table [default: 65536, 4096 for adversarial]
+  --delta D             fraction of slots kept free, in (0, 1) [default: the builder's, 1/8]
+  --strategy S          linear, quadratic, double-hash or uniform [default: uniform]
+  --prng P              lcg, splitmix64, pcg or xorshift-star [default: lcg]
+  --hasher H            default (SipHash, fixed keys), seeded or random (SipHash, random
+                        keys) [default: default]
+
+Workload:
+  --workload W          uniform, sequential, zipf, strings or adversarial [default: uniform];
+                        finding an adversarial key takes about slots / 2 hashes
+  --key-space N         keys are drawn from [0, N) [default: 4 * slots]
+  --skew S              Zipf exponent [default: 0.99]
+  --key-len MIN..=MAX   length of string keys [default: 8..=16]
+  --mix M               read-heavy, balanced, insert-only, churn or READS/INSERTS/REMOVES
+                        weights such as 80/15/5 [default: read-heavy]
+  --prefill N           keys inserted before the timed run [default: half the capacity]
+  --ops N               timed operations [default: 1000000]
+  --seed N              seed of the keys, the operations and the seeded hasher [default: 0]
+
+Output:
+  --baselines LIST      comma-separated baselines to run too: std or none [default: std]
+  --format F            table, csv or json [default: table]
+  --output FILE         write to FILE instead of stdout
+  -h, --help            print this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HasherKind {
+    Default,
+    Seeded,
+    Random,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Workload {
+    Uniform,
+    Sequential,
+    Zipf,
+    Strings,
+    Adversarial,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    /// `None` picks a size that suits the workload, see `slots`
+    slots: Option<usize>,
+    /// `None` keeps the default of the builder
+    delta: Option<f64>,
+    strategy: ProbeStrategy,
+    prng: Prng,
+    hasher: HasherKind,
+    workload: Workload,
+    key_space: Option<u64>,
+    skew: f64,
+    key_len: (usize, usize),
+    mix: OpMix,
+    prefill: Option<usize>,
+    ops: usize,
+    seed: u64,
+    std_baseline: bool,
+    format: Format,
+    output: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            slots: None,
+            delta: None,
+            strategy: ProbeStrategy::Uniform,
+            prng: Prng::default(),
+            hasher: HasherKind::Default,
+            workload: Workload::Uniform,
+            key_space: None,
+            skew: 0.99,
+            key_len: (8, 16),
+            mix: OpMix::READ_HEAVY,
+            prefill: None,
+            ops: 1_000_000,
+            seed: 0,
+            std_baseline: true,
+            format: Format::Table,
+            output: None,
+        }
+    }
+}
+
+impl Config {
+    fn slots(&self) -> usize {
+        match (self.slots, self.workload) {
+            (Some(slots), _) => slots,
+            // every colliding key is searched for among about half the
+            // slots, a large table takes minutes to fill
+            (None, Workload::Adversarial) => 1 << 12,
+            (None, _) => 1 << 16,
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .replace('_', "")
+        .parse()
+        .map_err(|_| format!("{}: invalid number {:?}", flag, value))
+}
+
+fn parse_mix(value: &str) -> Result<OpMix, String> {
+    match value {
+        "read-heavy" => return Ok(OpMix::READ_HEAVY),
+        "balanced" => return Ok(OpMix::BALANCED),
+        "insert-only" => return Ok(OpMix::INSERT_ONLY),
+        "churn" => return Ok(OpMix::CHURN),
+        _ => {}
+    }
+    let weights = value
+        .split('/')
+        .map(|weight| parse_number::<u32>("--mix", weight))
+        .collect::<Result<Vec<_>, _>>()?;
+    match weights[..] {
+        [reads, inserts, removes] if reads as u64 + inserts as u64 + removes as u64 > 0 => {
+            Ok(OpMix::new(reads, inserts, removes))
+        }
+        _ => Err(format!(
+            "--mix: expected a preset or READS/INSERTS/REMOVES, got {:?}",
+            value
+        )),
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Config>, String> {
+    let mut config = Config::default();
+    while let Some(flag) = args.next() {
+        if flag == "-h" || flag == "--help" {
+            return Ok(None);
+        }
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{}: missing value", flag))?;
+        let invalid = || format!("{}: invalid value {:?}", flag, value);
+        match flag.as_str() {
+            "--slots" => config.slots = Some(parse_number(&flag, &value)?),
+            "--delta" => config.delta = Some(parse_number(&flag, &value)?),
+            "--strategy" => {
+                config.strategy = ProbeStrategy::from_name(&value).ok_or_else(invalid)?
+            }
+            "--prng" => config.prng = Prng::from_name(&value).ok_or_else(invalid)?,
+            "--hasher" => {
+                config.hasher = match value.as_str() {
+                    "default" => HasherKind::Default,
+                    "seeded" => HasherKind::Seeded,
+                    "random" => HasherKind::Random,
+                    _ => return Err(invalid()),
+                }
+            }
+            "--workload" => {
+                config.workload = match value.as_str() {
+                    "uniform" => Workload::Uniform,
+                    "sequential" => Workload::Sequential,
+                    "zipf" => Workload::Zipf,
+                    "strings" => Workload::Strings,
+                    "adversarial" => Workload::Adversarial,
+                    _ => return Err(invalid()),
+                }
+            }
+            "--key-space" => config.key_space = Some(parse_number(&flag, &value)?),
+            "--skew" => config.skew = parse_number(&flag, &value)?,
+            "--key-len" => {
+                let (min, max) = value.split_once("..=").ok_or_else(invalid)?;
+                config.key_len = (parse_number(&flag, min)?, parse_number(&flag, max)?);
+                if config.key_len.0 > config.key_len.1 {
+                    return Err(invalid());
+                }
+            }
+            "--mix" => config.mix = parse_mix(&value)?,
+            "--prefill" => config.prefill = Some(parse_number(&flag, &value)?),
+            "--ops" => config.ops = parse_number(&flag, &value)?,
+            "--seed" => config.seed = parse_number(&flag, &value)?,
+            "--baselines" => {
+                config.std_baseline = false;
+                for baseline in value.split(',') {
+                    match baseline {
+                        "std" => config.std_baseline = true,
+                        "none" => {}
+                        _ => return Err(invalid()),
+                    }
+                }
+            }
+            "--format" => {
+                config.format = match value.as_str() {
+                    "table" => Format::Table,
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    _ => return Err(invalid()),
+                }
+            }
+            "--output" => config.output = Some(value),
+            _ => return Err(format!("unknown flag {:?}", flag)),
+        }
+    }
+    if config.key_space == Some(0) {
+        return Err("--key-space: must be greater than 0".to_string());
+    }
+    if config.skew < 0.0 {
+        return Err("--skew: must not be negative".to_string());
+    }
+    Ok(Some(config))
+}
+
+/// Measurements of all maps
+struct Report {
+    /// δ of the elastic map, which may be the default of the builder
+    delta: f64,
+    rows: Vec<Row>,
+}
+
+/// Measurements of one map
+struct Row {
+    map: &'static str,
+    seconds: f64,
+    /// `None` for maps that do not count their probes
+    probes_per_op: Option<f64>,
+    memory_bytes: usize,
+    len: usize,
+}
+
+impl Row {
+    fn mops(&self, config: &Config) -> f64 {
+        config.ops as f64 / self.seconds / 1e6
+    }
+}
+
+/// Apply `ops` to `map`, returns a checksum of the results so the work is
+/// not optimized out
+fn apply<K, M: TraceTarget<K, u64>>(map: &mut M, ops: &[Op<K>]) -> u64
+where
+    K: Clone,
+{
+    let mut checksum = 0;
+    for (i, op) in ops.iter().enumerate() {
+        let result = match op {
+            Op::Get(key) => map.get(key).copied(),
+            Op::Insert(key) => map.insert(key.clone(), i as u64),
+            Op::Remove(key) => map.remove(key),
+        };
+        checksum ^= result.unwrap_or(u64::MAX);
+    }
+    checksum
+}
+
+/// Apply `ops` to `map`, which records probe statistics, and return the
+/// groups examined.
+///
+/// Every operation looks its key up once. The probes of an insert that
+/// stores a new key include that lookup, which was recorded as a miss too,
+/// so it is taken out of the misses.
+fn count_probes<K, S>(map: &mut ElasticHashMap<K, u64, NoHashCache, S>, ops: &[Op<K>]) -> u64
+where
+    K: Clone + Eq + Hash,
+    S: BuildHasher + HasherSeed,
+{
+    const ENABLED: &str = "probe stats are enabled";
+    let total =
+        |stats: &ProbeStats| stats.insert_total().sum() + stats.hits().sum() + stats.misses().sum();
+    let start = total(map.probe_stats().expect(ENABLED));
+    let mut insert_lookups = 0;
+    for op in ops {
+        let len = map.len();
+        let misses = map.probe_stats().expect(ENABLED).misses().sum();
+        apply(map, std::slice::from_ref(op));
+        if matches!(op, Op::Insert(_)) && map.len() > len {
+            insert_lookups += map.probe_stats().expect(ENABLED).misses().sum() - misses;
+        }
+    }
+    total(map.probe_stats().expect(ENABLED)) - start - insert_lookups
+}
+
+fn timed<R>(run: impl FnOnce() -> R) -> f64 {
+    let start = Instant::now();
+    black_box(run());
+    start.elapsed().as_secs_f64()
+}
+
+/// Bytes std's `HashMap` allocates for `capacity` entries, estimated from
+/// the layout of its SwissTable: a power of two of buckets at most 7/8
+/// full, one control byte per bucket and a group of trailing control bytes
+fn std_allocation_size<K>(capacity: usize) -> usize {
+    if capacity == 0 {
+        return 0;
+    }
+    let buckets = match capacity {
+        0..=3 => 4,
+        4..=7 => 8,
+        _ => (capacity * 8).div_ceil(7).next_power_of_two(),
+    };
+    buckets * (std::mem::size_of::<(K, u64)>() + 1) + 16
+}
+
+fn run<K, S>(
+    config: &Config,
+    builder: &ElasticHashMapBuilder<S>,
+    hasher: S,
+    prefill: usize,
+    keys: impl Iterator<Item = K>,
+) -> Result<Vec<Row>, String>
+where
+    K: Clone + Eq + Hash,
+    S: BuildHasher + HasherSeed + Clone,
+{
+    let mut operations = Operations::new(config.seed, OpMix::INSERT_ONLY, keys);
+    let fill: Vec<Op<K>> = operations.by_ref().take(prefill).collect();
+    operations.set_mix(config.mix);
+    let ops: Vec<Op<K>> = operations.take(config.ops).collect();
+    if fill.len() < prefill || ops.len() < config.ops {
+        return Err("the workload ran out of keys".to_string());
+    }
+
+    let build = || -> Result<ElasticHashMap<K, u64, NoHashCache, S>, String> {
+        builder.clone().build().map_err(|err| err.to_string())
+    };
+    let mut rows = Vec::new();
+
+    let mut map = build()?;
+    apply(&mut map, &fill);
+    let seconds = timed(|| apply(&mut map, &ops));
+    let mut counted = build()?;
+    apply(&mut counted, &fill);
+    counted.enable_probe_stats();
+    let probes = count_probes(&mut counted, &ops);
+    rows.push(Row {
+        map: "elastic",
+        seconds,
+        probes_per_op: Some(probes as f64 / ops.len().max(1) as f64),
+        memory_bytes: map.allocation_size(),
+        len: map.len(),
+    });
+
+    if config.std_baseline {
+        let capacity = build()?.capacity();
+        let mut map = HashMap::with_capacity_and_hasher(capacity, hasher);
+        apply(&mut map, &fill);
+        let seconds = timed(|| apply(&mut map, &ops));
+        rows.push(Row {
+            map: "std",
+            seconds,
+            probes_per_op: None,
+            memory_bytes: std_allocation_size::<K>(map.capacity()),
+            len: map.len(),
+        });
+    }
+    Ok(rows)
+}
+
+fn bench<S: BuildHasher + HasherSeed + Clone>(
+    config: &Config,
+    hasher: S,
+) -> Result<Report, String> {
+    let mut builder = ElasticHashMapBuilder::new()
+        .slots(config.slots())
+        .probe_strategy(config.strategy)
+        .prng(config.prng)
+        .hasher(hasher.clone());
+    if let Some(delta) = config.delta {
+        builder = builder.delta(delta);
+    }
+    let simulator = builder.build_simulator().map_err(|err| err.to_string())?;
+    let prefill = config.prefill.unwrap_or(simulator.capacity() / 2);
+    let key_space = config.key_space.unwrap_or(4 * config.slots() as u64);
+    let seed = config.seed;
+    let rows = match config.workload {
+        Workload::Uniform => run(
+            config,
+            &builder,
+            hasher.clone(),
+            prefill,
+            Keys::uniform(seed, key_space),
+        ),
+        Workload::Sequential => run(
+            config,
+            &builder,
+            hasher.clone(),
+            prefill,
+            Keys::sequential(0),
+        ),
+        Workload::Zipf => run(
+            config,
+            &builder,
+            hasher.clone(),
+            prefill,
+            Keys::zipf(seed, key_space, config.skew),
+        ),
+        Workload::Strings => {
+            let (min, max) = config.key_len;
+            run(
+                config,
+                &builder,
+                hasher.clone(),
+                prefill,
+                StringKeys::new(seed, min..=max),
+            )
+        }
+        // keys that share a slot of the largest bucket under `hash % len`
+        Workload::Adversarial => run(
+            config,
+            &builder,
+            hasher.clone(),
+            prefill,
+            CollidingKeys::new(hasher, simulator.bucket_size(0), 0, 0),
+        ),
+    }?;
+    Ok(Report {
+        delta: simulator.delta(),
+        rows,
+    })
+}
+
+fn name<T: std::fmt::Debug>(value: T) -> String {
+    format!("{:?}", value).to_lowercase()
+}
+
+fn mix_name(mix: OpMix) -> String {
+    format!("{}/{}/{}", mix.reads, mix.inserts, mix.removes)
+}
+
+fn render(config: &Config, report: &Report) -> String {
+    let (delta, rows) = (report.delta, &report.rows);
+    let mut out = String::new();
+    let probes = |row: &Row| row.probes_per_op.map(|p| format!("{:.3}", p));
+    match config.format {
+        Format::Table => {
+            writeln!(
+                out,
+                "{} slots, δ = {}, {} probing, {} hasher, {} keys, mix {}, {} ops",
+                config.slots(),
+                delta,
+                config.strategy.name(),
+                name(config.hasher),
+                name(config.workload),
+                mix_name(config.mix),
+                config.ops
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "{:<8} {:>10} {:>10} {:>10} {:>14} {:>10}",
+                "map", "seconds", "Mops/s", "probes/op", "memory bytes", "len"
+            )
+            .unwrap();
+            for row in rows {
+                writeln!(
+                    out,
+                    "{:<8} {:>10.4} {:>10.2} {:>10} {:>14} {:>10}",
+                    row.map,
+                    row.seconds,
+                    row.mops(config),
+                    probes(row).unwrap_or_else(|| "-".to_string()),
+                    row.memory_bytes,
+                    row.len
+                )
+                .unwrap();
+            }
+        }
+        Format::Csv => {
+            writeln!(
+                out,
+                "map,slots,delta,strategy,hasher,workload,mix,ops,seconds,mops,probes_per_op,memory_bytes,len"
+            )
+            .unwrap();
+            for row in rows {
+                writeln!(
+                    out,
+                    "{},{},{},{},{},{},{},{},{:.6},{:.4},{},{},{}",
+                    row.map,
+                    config.slots(),
+                    delta,
+                    config.strategy.name(),
+                    name(config.hasher),
+                    name(config.workload),
+                    mix_name(config.mix),
+                    config.ops,
+                    row.seconds,
+                    row.mops(config),
+                    probes(row).unwrap_or_default(),
+                    row.memory_bytes,
+                    row.len
+                )
+                .unwrap();
+            }
+        }
+        Format::Json => {
+            let results: Vec<String> = rows
+                .iter()
+                .map(|row| {
+                    format!(
+                        "{{\"map\":\"{}\",\"seconds\":{:.6},\"mops\":{:.4},\"probes_per_op\":{},\"memory_bytes\":{},\"len\":{}}}",
+                        row.map,
+                        row.seconds,
+                        row.mops(config),
+                        probes(row).unwrap_or_else(|| "null".to_string()),
+                        row.memory_bytes,
+                        row.len
+                    )
+                })
+                .collect();
+            writeln!(
+                out,
+                "{{\"config\":{{\"slots\":{},\"delta\":{},\"strategy\":\"{}\",\"hasher\":\"{}\",\"workload\":\"{}\",\"mix\":\"{}\",\"ops\":{},\"seed\":{}}},\"results\":[{}]}}",
+                config.slots(),
+                delta,
+                config.strategy.name(),
+                name(config.hasher),
+                name(config.workload),
+                mix_name(config.mix),
+                config.ops,
+                config.seed,
+                results.join(",")
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+fn main() -> ExitCode {
+    let config = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(config)) => config,
+        Ok(None) => {
+            print!("{}", USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("elastic-bench: {}\n\n{}", err, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+    let report = match config.hasher {
+        HasherKind::Default => bench(&config, DefaultHashBuilder::default()),
+        HasherKind::Seeded => bench(&config, SeededState::new(config.seed)),
+        HasherKind::Random => bench(&config, RandomState::new()),
+    };
+    let report = match report {
+        Ok(report) => report,
+        Err(err) => {
+            eprintln!("elastic-bench: {}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let out = render(&config, &report);
+    let written = match &config.output {
+        Some(path) => std::fs::write(path, out).map_err(|err| format!("{}: {}", path, err)),
+        None => std::io::stdout()
+            .write_all(out.as_bytes())
+            .map_err(|err| err.to_string()),
+    };
+    match written {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("elastic-bench: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Config>, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let config = parse(&[]).unwrap().unwrap();
+        assert_eq!(config.delta, None);
+        assert_eq!(config.mix, OpMix::READ_HEAVY);
+        assert_eq!(config.strategy, ProbeStrategy::Uniform);
+        assert!(config.std_baseline);
+        assert_eq!(config.slots(), 1 << 16);
+        let config = parse(&["--workload", "adversarial"]).unwrap().unwrap();
+        assert_eq!(config.slots(), 1 << 12);
+
+        let config = parse(&[
+            "--slots",
+            "1_048_576",
+            "--delta",
+            "0.01",
+            "--strategy",
+            "double-hash",
+            "--prng",
+            "pcg",
+            "--hasher",
+            "seeded",
+            "--workload",
+            "strings",
+            "--key-len",
+            "4..=32",
+            "--mix",
+            "churn",
+            "--baselines",
+            "none",
+            "--format",
+            "json",
+        ])
+        .unwrap()
+        .unwrap();
+        assert_eq!(config.slots(), 1 << 20);
+        assert_eq!(config.delta, Some(0.01));
+        assert_eq!(config.strategy, ProbeStrategy::DoubleHash);
+        assert_eq!(config.prng, Prng::Pcg);
+        assert_eq!(config.hasher, HasherKind::Seeded);
+        assert_eq!(config.workload, Workload::Strings);
+        assert_eq!(config.key_len, (4, 32));
+        assert_eq!(config.mix, OpMix::CHURN);
+        assert!(!config.std_baseline);
+        assert_eq!(config.format, Format::Json);
+
+        assert!(parse(&["--ops", "10", "--help"]).unwrap().is_none());
+        for args in [
+            &["--slots"][..],
+            &["--slots", "many"],
+            &["--strategy", "cuckoo"],
+            &["--key-len", "16..=8"],
+            &["--key-len", "8-16"],
+            &["--key-space", "0"],
+            &["--skew", "-1"],
+            &["--baselines", "std,btree"],
+            &["--verbose", "1"],
+        ] {
+            assert!(parse(args).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_parse_mix() {
+        assert_eq!(parse_mix("read-heavy"), Ok(OpMix::READ_HEAVY));
+        assert_eq!(parse_mix("balanced"), Ok(OpMix::BALANCED));
+        assert_eq!(parse_mix("insert-only"), Ok(OpMix::INSERT_ONLY));
+        assert_eq!(parse_mix("churn"), Ok(OpMix::CHURN));
+        assert_eq!(parse_mix("80/15/5"), Ok(OpMix::new(80, 15, 5)));
+        assert_eq!(parse_mix("0/1/0"), Ok(OpMix::INSERT_ONLY));
+        for mix in ["0/0/0", "80/20", "1/2/3/4", "a/b/c", "-1/1/1", ""] {
+            assert!(parse_mix(mix).is_err(), "{:?}", mix);
+        }
+
+        // the operations follow the weights once there are keys to read
+        let config = parse(&["--mix", "60/30/10"]).unwrap().unwrap();
+        let mut operations = Operations::new(1, OpMix::INSERT_ONLY, Keys::sequential(0));
+        operations.by_ref().take(1000).for_each(drop);
+        operations.set_mix(config.mix);
+        let mut counts = [0u32; 3];
+        for op in operations.take(10000) {
+            counts[match op {
+                Op::Get(_) => 0,
+                Op::Insert(_) => 1,
+                Op::Remove(_) => 2,
+            }] += 1;
+        }
+        for (count, weight) in counts.into_iter().zip([60, 30, 10]) {
+            assert!(count.abs_diff(weight * 100) < 300, "{:?}", counts);
+        }
+    }
+
+    #[test]
+    fn test_count_probes() {
+        let mut map: ElasticHashMap<u64, u64> =
+            ElasticHashMapBuilder::new().capacity(2000).build().unwrap();
+        map.enable_probe_stats();
+        let mut operations = Operations::new(0, OpMix::INSERT_ONLY, Keys::sequential(0));
+        let fill: Vec<_> = operations.by_ref().take(1500).collect();
+        // the lookup of every insert is part of its probes already
+        let probes = count_probes(&mut map, &fill);
+        let stats = map.probe_stats().unwrap();
+        assert_eq!(probes, stats.insert_total().sum());
+        assert!(probes >= fill.len() as u64);
+
+        operations.set_mix(OpMix::new(1, 0, 0));
+        let reads: Vec<_> = operations.by_ref().take(1000).collect();
+        let hits = map.probe_stats().unwrap().hits().sum();
+        let probes = count_probes(&mut map, &reads);
+        assert_eq!(probes, map.probe_stats().unwrap().hits().sum() - hits);
+        assert!(probes >= reads.len() as u64);
+
+        // replacing a value is a hit, not an insert
+        let replaces: Vec<_> = fill.iter().take(100).cloned().collect();
+        let stats = map.probe_stats().unwrap();
+        let (inserts, hits) = (stats.insert_total().sum(), stats.hits().sum());
+        let probes = count_probes(&mut map, &replaces);
+        let stats = map.probe_stats().unwrap();
+        assert_eq!(stats.insert_total().sum(), inserts);
+        assert_eq!(probes, stats.hits().sum() - hits);
+    }
+}
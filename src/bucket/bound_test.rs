@@ -0,0 +1,231 @@
+//! Probe complexity against the bounds of the paper.
+//!
+//! Elastic hashing fills a table to `1 - δ` with O(1) amortized expected
+//! probes per insert, and O(log δ⁻¹) worst-case expected probes for any
+//! single insert or lookup. These tests fill tables of several sizes for
+//! δ = 1/2 … 1/1024 and check the measured probe counts against constant
+//! multiples of those bounds.
+//!
+//! The paper counts the probes of the insertion's own probe sequences, the
+//! lookup that precedes an insert here is not part of it. An insert is
+//! charged the groups examined in the buckets it probes, taken from
+//! `explain`, lookups the groups examined by `get`.
+//!
+//! They take a while, run them with
+//!
+//! ```text
+//! cargo test --release -- --ignored probe_bounds
+//! ```
+//!
+//! Every measurement is written to `target/probe-bounds.csv`, or to the file
+//! named by `PROBE_BOUNDS_CSV`.
+
+use std::{cell::RefCell, fmt::Write};
+
+use super::*;
+
+const SIZES: [usize; 3] = [1 << 10, 1 << 12, 1 << 14];
+const DELTA_FACTORS: std::ops::RangeInclusive<i32> = 1..=10;
+const TRIALS: u64 = 2;
+/// The worst case is estimated as the mean of the costliest of this many
+/// runs of consecutive operations, by insertion order
+const SEGMENTS: usize = 32;
+
+/// Amortized probes per insert, whatever δ
+const AMORTIZED_INSERT: f64 = 4.0;
+/// Worst-case probes per insert or lookup, per bit of `log₂ δ⁻¹`
+const WORST_CASE: f64 = 6.0;
+
+/// Keeps the probe count of every successful lookup, in order
+#[derive(Default)]
+struct LookupLog {
+    probes: RefCell<Vec<u32>>,
+}
+
+impl ElasticObserver for LookupLog {
+    fn on_lookup(&self, found: bool, probes: u32) {
+        // every insert looks its key up first
+        if found {
+            self.probes.borrow_mut().push(probes);
+        }
+    }
+}
+
+/// Groups an insert examines in the buckets its probe sequences cover: the
+/// bucket the value lands in, and the one it gave up on before
+fn insert_probes(trace: &ProbeTrace) -> u32 {
+    let TraceOutcome::Insert { bucket, .. } = trace.outcome else {
+        panic!("the table is full: {:?}", trace.outcome);
+    };
+    let probed = |step: &ProbeStep| {
+        step.bucket == bucket || (step.phase == Phase::Insert && step.bucket + 1 == bucket)
+    };
+    trace
+        .steps
+        .iter()
+        .filter(|step| step.group.is_some() && probed(step))
+        .count() as u32
+}
+
+/// Probe counts of one configuration, averaged over the trials
+struct Measurement {
+    slots: usize,
+    delta_factor: i32,
+    elements: usize,
+    insert_mean: f64,
+    insert_worst: f64,
+    lookup_mean: f64,
+    lookup_worst: f64,
+}
+
+impl Measurement {
+    const CSV_HEADER: &'static str =
+        "slots,delta,elements,insert_mean,insert_worst,lookup_mean,lookup_worst";
+
+    fn to_csv(&self) -> String {
+        format!(
+            "{},{},{},{:.4},{:.4},{:.4},{:.4}",
+            self.slots,
+            1.0 / (1u64 << self.delta_factor) as f64,
+            self.elements,
+            self.insert_mean,
+            self.insert_worst,
+            self.lookup_mean,
+            self.lookup_worst
+        )
+    }
+}
+
+fn mean(probes: &[u32]) -> f64 {
+    probes.iter().map(|&p| p as f64).sum::<f64>() / probes.len() as f64
+}
+
+/// Mean of every segment of `probes`
+fn segment_means(probes: &[u32]) -> Vec<f64> {
+    let len = probes.len().div_ceil(SEGMENTS);
+    probes.chunks(len).map(mean).collect()
+}
+
+/// Fill a table of `slots` slots to `1 - δ` `TRIALS` times, then look every
+/// key up
+fn measure(slots: usize, delta_factor: i32) -> Measurement {
+    let mut insert_means = Vec::new();
+    let mut lookup_means = Vec::new();
+    let mut insert_segments = vec![0.0; SEGMENTS];
+    let mut lookup_segments = vec![0.0; SEGMENTS];
+    let mut elements = 0;
+    for trial in 0..TRIALS {
+        let mut map: ElasticHashMap<u64, u64, NoHashCache, DefaultHashBuilder, LookupLog> =
+            ElasticHashMapBuilder::new()
+                .slots(slots)
+                .delta_factor(delta_factor)
+                .observer(LookupLog::default())
+                .build()
+                .unwrap();
+        elements = map.capacity();
+        let keys = (0..elements as u64).map(|k| k + (trial << 32));
+        let mut inserts = Vec::with_capacity(elements);
+        for key in keys.clone() {
+            inserts.push(insert_probes(&map.explain(&key)));
+            map.insert(key, key);
+        }
+        assert_eq!(map.get_underlying_size(), slots, "the table grew");
+        for key in keys {
+            assert_eq!(map.get(&key), Some(&key));
+        }
+
+        let lookups = map.observer().probes.borrow();
+        insert_means.push(mean(&inserts));
+        lookup_means.push(mean(&lookups));
+        for (total, segment) in insert_segments.iter_mut().zip(segment_means(&inserts)) {
+            *total += segment / TRIALS as f64;
+        }
+        for (total, segment) in lookup_segments.iter_mut().zip(segment_means(&lookups)) {
+            *total += segment / TRIALS as f64;
+        }
+    }
+    let max = |segments: Vec<f64>| segments.into_iter().fold(0.0, f64::max);
+    Measurement {
+        slots,
+        delta_factor,
+        elements,
+        insert_mean: insert_means.iter().sum::<f64>() / TRIALS as f64,
+        insert_worst: max(insert_segments),
+        lookup_mean: lookup_means.iter().sum::<f64>() / TRIALS as f64,
+        lookup_worst: max(lookup_segments),
+    }
+}
+
+fn write_csv(measurements: &[Measurement]) {
+    let path = std::env::var("PROBE_BOUNDS_CSV").unwrap_or_else(|_| {
+        concat!(env!("CARGO_MANIFEST_DIR"), "/target/probe-bounds.csv").to_string()
+    });
+    let mut csv = String::new();
+    writeln!(csv, "{}", Measurement::CSV_HEADER).unwrap();
+    for measurement in measurements {
+        writeln!(csv, "{}", measurement.to_csv()).unwrap();
+    }
+    std::fs::write(&path, csv).unwrap_or_else(|err| panic!("writing {}: {}", path, err));
+    println!("probe counts written to {}", path);
+}
+
+#[test]
+#[ignore = "slow, run with --release -- --ignored"]
+pub(crate) fn test_probe_bounds() {
+    let mut measurements = Vec::new();
+    for slots in SIZES {
+        for delta_factor in DELTA_FACTORS {
+            let measurement = measure(slots, delta_factor);
+            println!("{}", measurement.to_csv());
+            measurements.push(measurement);
+        }
+    }
+    write_csv(&measurements);
+
+    for m in &measurements {
+        // log₂ δ⁻¹ is the delta factor
+        let worst_case = WORST_CASE * m.delta_factor.max(1) as f64;
+        assert!(
+            m.insert_mean <= AMORTIZED_INSERT,
+            "{} slots, δ = 1/2^{}: {:.2} probes per insert, more than O(1) = {}",
+            m.slots,
+            m.delta_factor,
+            m.insert_mean,
+            AMORTIZED_INSERT
+        );
+        assert!(
+            m.insert_worst <= worst_case,
+            "{} slots, δ = 1/2^{}: worst inserts took {:.2} probes, more than O(log δ⁻¹) = {}",
+            m.slots,
+            m.delta_factor,
+            m.insert_worst,
+            worst_case
+        );
+        assert!(
+            m.lookup_worst <= worst_case,
+            "{} slots, δ = 1/2^{}: worst lookups took {:.2} probes, more than O(log δ⁻¹) = {}",
+            m.slots,
+            m.delta_factor,
+            m.lookup_worst,
+            worst_case
+        );
+    }
+
+    // the amortized cost does not grow with δ⁻¹ at a fixed size
+    for slots in SIZES {
+        let means: Vec<f64> = measurements
+            .iter()
+            .filter(|m| m.slots == slots)
+            .map(|m| m.insert_mean)
+            .collect();
+        let first = means[0];
+        let last = means[means.len() - 1];
+        assert!(
+            last <= first * 2.0 + 1.0,
+            "{} slots: {:.2} probes per insert at δ = 1/1024, {:.2} at δ = 1/2",
+            slots,
+            last,
+            first
+        );
+    }
+}
@@ -0,0 +1,425 @@
+//! Builder exposing the tuning parameters of the elastic hashing scheme
+
+use std::{fmt, hash::BuildHasher};
+
+use super::{
+    hash_cache::HashCache,
+    map::{DefaultHashBuilder, ElasticHashMap},
+    observer::{ElasticObserver, NoObserver},
+    simulator::ElasticSimulator,
+    table::ElasticHashTable,
+    utils::{delta, power_of_two_slots_for, slots_for},
+};
+use crate::probe::{Prng, ProbeStrategy};
+
+/// Tuning parameters of an elastic hash table
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Params {
+    /// Fraction of slots left free when the table is considered full
+    pub(crate) delta: f64,
+    /// Fill fraction a bucket reaches in the batch that first uses it
+    pub(crate) batch_fill: f32,
+    /// Constant `C` of the probe limit `f(ε) = C · min(log²(1/ε), log(1/δ))`
+    pub(crate) probe_constant: f32,
+    /// Tombstones found within this many attempts of a bucket are reused
+    pub(crate) tombstone_scan: u32,
+    /// Size of each bucket relative to the previous one
+    pub(crate) bucket_ratio: f32,
+    /// Buckets smaller than this are merged into the last bucket
+    pub(crate) min_bucket_size: usize,
+    /// Split the table into at most this many buckets
+    pub(crate) bucket_count: Option<usize>,
+    /// Round slot counts up to a power of two instead of a whole number of
+    /// groups
+    pub(crate) power_of_two: bool,
+    pub(crate) strategy: ProbeStrategy,
+    /// Generator behind uniform probing
+    pub(crate) prng: Prng,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            delta: delta(3),
+            batch_fill: 0.75,
+            probe_constant: 3000.0,
+            tombstone_scan: 5,
+            bucket_ratio: 0.5,
+            min_bucket_size: 1,
+            bucket_count: None,
+            power_of_two: false,
+            strategy: ProbeStrategy::Uniform,
+            prng: Prng::default(),
+        }
+    }
+}
+
+impl Params {
+    /// Number of slots a table needs to hold `elements` values, a power of
+    /// two when asked for or when the probe strategy needs one
+    pub(crate) fn slots_for(&self, elements: usize) -> usize {
+        if self.power_of_two || self.needs_power_of_two() {
+            power_of_two_slots_for(elements, self.delta)
+        } else {
+            slots_for(elements, self.delta)
+        }
+    }
+
+    /// Split `size` slots into buckets, each `bucket_ratio` times the size
+    /// of the previous one.
+    ///
+    /// The remaining slots form the last bucket once the next one would be
+    /// smaller than `min_bucket_size`, or the bucket count is reached.
+    pub(crate) fn bucket_sizes(&self, size: usize) -> Vec<usize> {
+        let shrink = 1.0 - self.bucket_ratio as f64;
+        let mut sizes = Vec::new();
+        let mut remaining_size = size;
+        while remaining_size > 0 {
+            let mut current_size = (remaining_size as f64 * shrink).ceil() as usize;
+            let is_last = self
+                .bucket_count
+                .is_some_and(|count| sizes.len() + 1 >= count);
+            if is_last
+                || current_size < self.min_bucket_size
+                || remaining_size - current_size < self.min_bucket_size
+            {
+                current_size = remaining_size;
+            }
+            sizes.push(current_size);
+            remaining_size = remaining_size.saturating_sub(current_size);
+        }
+        sizes
+    }
+
+    /// Load of a bucket of `bucket_size` slots once the batch that first
+    /// uses it is done
+    pub(crate) fn fill_target(&self, bucket_size: usize) -> usize {
+        (bucket_size as f64 * self.batch_fill as f64).ceil() as usize
+    }
+
+    /// Load of a bucket of `bucket_size` slots once the batch after that is
+    /// done too, leaving `δ/2` of its slots free
+    pub(crate) fn full_target(&self, bucket_size: usize) -> usize {
+        bucket_size - (bucket_size as f64 * self.delta / 2.0).floor() as usize
+    }
+
+    /// Number of empty slots every batch fills in buckets of `sizes`, one
+    /// more batch than buckets as the last batch fills the last bucket up
+    pub(crate) fn batch_sizes(&self, sizes: &[usize]) -> Vec<usize> {
+        let fill = |i: usize| sizes.get(i).map_or(0, |&size| self.fill_target(size));
+        (0..=sizes.len())
+            .map(|i| match i {
+                0 => fill(0),
+                i => self.full_target(sizes[i - 1]) - fill(i - 1) + fill(i),
+            })
+            .collect()
+    }
+
+    /// Probe limit `f(ε)` of a bucket with free fraction `epsilon`
+    pub(crate) fn probe_limit(&self, epsilon: f64) -> i32 {
+        (self.probe_constant as f64
+            * f64::min(epsilon.recip().ln().powi(2), self.delta.recip().ln())) as i32
+    }
+
+    /// Whether the probe strategy only reaches every group of a bucket whose
+    /// size is a power of two
+    fn needs_power_of_two(&self) -> bool {
+        matches!(
+            self.strategy,
+            ProbeStrategy::Quadratic | ProbeStrategy::DoubleHash
+        )
+    }
+
+    /// Check that the batch schedule can be followed with these parameters
+    pub(crate) fn validate(&self) -> Result<(), BuildError> {
+        if !(self.delta > 0.0 && self.delta < 1.0) {
+            return Err(BuildError::InvalidDelta(self.delta));
+        }
+        // a batch fills its bucket from `batch_fill` up to `1 - δ/2`
+        if !(self.batch_fill > 0.0 && self.batch_fill as f64 <= 1.0 - self.delta / 2.0) {
+            return Err(BuildError::InvalidBatchFill(self.batch_fill));
+        }
+        if !(self.probe_constant.is_finite() && self.probe_constant > 0.0) {
+            return Err(BuildError::InvalidProbeConstant(self.probe_constant));
+        }
+        if !(self.bucket_ratio > 0.0 && self.bucket_ratio < 1.0) {
+            return Err(BuildError::InvalidBucketRatio(self.bucket_ratio));
+        }
+        if self.min_bucket_size == 0 {
+            return Err(BuildError::InvalidMinBucketSize);
+        }
+        if self.bucket_count == Some(0) {
+            return Err(BuildError::InvalidBucketCount);
+        }
+        if self.needs_power_of_two() && self.bucket_ratio != 0.5 {
+            return Err(BuildError::IncompatibleProbeStrategy(self.strategy));
+        }
+        Ok(())
+    }
+}
+
+/// Rejected parameter combination of an `ElasticHashMapBuilder`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BuildError {
+    /// δ is not in (0, 1)
+    InvalidDelta(f64),
+    /// The batch fill is not in (0, 1 - δ/2]
+    InvalidBatchFill(f32),
+    /// The probe constant is not a positive number
+    InvalidProbeConstant(f32),
+    /// The bucket ratio is not in (0, 1)
+    InvalidBucketRatio(f32),
+    /// The minimum bucket size is 0
+    InvalidMinBucketSize,
+    /// The bucket count is 0
+    InvalidBucketCount,
+    /// The table would have no slots
+    ZeroSlots,
+    /// The probe strategy cannot reach every slot of the bucket geometry
+    IncompatibleProbeStrategy(ProbeStrategy),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::InvalidDelta(delta) => write!(f, "delta {} is not in (0, 1)", delta),
+            BuildError::InvalidBatchFill(fill) => {
+                write!(f, "batch fill {} is not in (0, 1 - delta / 2]", fill)
+            }
+            BuildError::InvalidProbeConstant(c) => {
+                write!(f, "probe constant {} is not a positive number", c)
+            }
+            BuildError::InvalidBucketRatio(ratio) => {
+                write!(f, "bucket ratio {} is not in (0, 1)", ratio)
+            }
+            BuildError::InvalidMinBucketSize => write!(f, "minimum bucket size is 0"),
+            BuildError::InvalidBucketCount => write!(f, "bucket count is 0"),
+            BuildError::ZeroSlots => write!(f, "table has no slots"),
+            BuildError::IncompatibleProbeStrategy(strategy) => {
+                write!(f, "{:?} probing needs power of two bucket sizes", strategy)
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuildError {}
+
+/// Builder for `ElasticHashMap` and `ElasticHashTable` with non-default
+/// tuning parameters.
+///
+/// Every parameter defaults to what `with_capacity` uses.
+#[derive(Debug, Clone)]
+pub struct ElasticHashMapBuilder<S = DefaultHashBuilder, O = NoObserver> {
+    capacity: usize,
+    slots: Option<usize>,
+    params: Params,
+    probe_stats: bool,
+    hash_builder: S,
+    observer: O,
+}
+
+impl ElasticHashMapBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Default for ElasticHashMapBuilder {
+    fn default() -> Self {
+        ElasticHashMapBuilder {
+            capacity: 16,
+            slots: None,
+            params: Params::default(),
+            probe_stats: false,
+            hash_builder: DefaultHashBuilder::default(),
+            observer: NoObserver,
+        }
+    }
+}
+
+impl<S, O> ElasticHashMapBuilder<S, O> {
+    /// Number of elements the table holds before it has to grow
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self.slots = None;
+        self
+    }
+
+    /// Exact number of slots, instead of deriving it from the capacity.
+    ///
+    /// Growing and shrinking then size the table by whole groups rather than
+    /// powers of two, unless the probe strategy needs them.
+    pub fn slots(mut self, slots: usize) -> Self {
+        self.slots = Some(slots);
+        self.params.power_of_two = false;
+        self
+    }
+
+    /// Whether slot counts are rounded up to a power of two rather than to a
+    /// whole number of groups, false by default. Quadratic and double-hash
+    /// probing always use powers of two.
+    pub fn power_of_two_size(mut self, power_of_two: bool) -> Self {
+        self.params.power_of_two = power_of_two;
+        self
+    }
+
+    /// δ = 1/2^`delta_factor`, the fraction of slots left free when the
+    /// table is considered full
+    pub fn delta_factor(mut self, delta_factor: i32) -> Self {
+        self.params.delta = delta(delta_factor);
+        self
+    }
+
+    /// Any δ in (0, 1), the fraction of slots left free when the table is
+    /// considered full
+    pub fn delta(mut self, delta: f64) -> Self {
+        self.params.delta = delta;
+        self
+    }
+
+    /// δ = `numerator / denominator`
+    pub fn delta_ratio(self, numerator: u32, denominator: u32) -> Self {
+        self.delta(numerator as f64 / denominator as f64)
+    }
+
+    /// Fill fraction a bucket reaches in the batch that first uses it,
+    /// 0.75 by default
+    pub fn batch_fill(mut self, batch_fill: f32) -> Self {
+        self.params.batch_fill = batch_fill;
+        self
+    }
+
+    /// Constant `C` of the probe limit `f(ε)`, 3000 by default
+    pub fn probe_constant(mut self, probe_constant: f32) -> Self {
+        self.params.probe_constant = probe_constant;
+        self
+    }
+
+    /// Reuse a tombstone when it is found within this many attempts of a
+    /// bucket, 5 by default. 0 never reuses tombstones.
+    pub fn tombstone_scan(mut self, attempts: u32) -> Self {
+        self.params.tombstone_scan = attempts;
+        self
+    }
+
+    /// Size of each bucket relative to the previous one, 0.5 by default
+    pub fn bucket_ratio(mut self, ratio: f32) -> Self {
+        self.params.bucket_ratio = ratio;
+        self
+    }
+
+    /// Merge the tail buckets that would be smaller than `min_size` slots
+    /// into the last bucket, 1 by default
+    pub fn min_bucket_size(mut self, min_size: usize) -> Self {
+        self.params.min_bucket_size = min_size;
+        self
+    }
+
+    /// Split the table into at most `count` buckets, the last one taking
+    /// all remaining slots
+    pub fn bucket_count(mut self, count: usize) -> Self {
+        self.params.bucket_count = Some(count);
+        self
+    }
+
+    pub fn probe_strategy(mut self, strategy: ProbeStrategy) -> Self {
+        self.params.strategy = strategy;
+        self
+    }
+
+    /// Draw the positions of uniform probing from `prng`, `Prng::Lcg` by
+    /// default
+    pub fn prng(mut self, prng: Prng) -> Self {
+        self.params.prng = prng;
+        self
+    }
+
+    /// Record probe-length histograms from the start, see `ProbeStats`
+    pub fn probe_stats(mut self, enabled: bool) -> Self {
+        self.probe_stats = enabled;
+        self
+    }
+
+    /// Hash keys with `hash_builder`
+    pub fn hasher<S2>(self, hash_builder: S2) -> ElasticHashMapBuilder<S2, O> {
+        ElasticHashMapBuilder {
+            capacity: self.capacity,
+            slots: self.slots,
+            params: self.params,
+            probe_stats: self.probe_stats,
+            hash_builder,
+            observer: self.observer,
+        }
+    }
+
+    /// Report the operations of the map to `observer`
+    pub fn observer<O2>(self, observer: O2) -> ElasticHashMapBuilder<S, O2>
+    where
+        O2: ElasticObserver,
+    {
+        ElasticHashMapBuilder {
+            capacity: self.capacity,
+            slots: self.slots,
+            params: self.params,
+            probe_stats: self.probe_stats,
+            hash_builder: self.hash_builder,
+            observer,
+        }
+    }
+
+    /// Number of slots of the table, checking the parameters
+    fn table_size(&self) -> Result<usize, BuildError> {
+        self.params.validate()?;
+        let size = match self.slots {
+            Some(0) => return Err(BuildError::ZeroSlots),
+            Some(slots) => slots,
+            None => self.params.slots_for(self.capacity),
+        };
+        if self.params.needs_power_of_two() && !size.is_power_of_two() {
+            return Err(BuildError::IncompatibleProbeStrategy(self.params.strategy));
+        }
+        Ok(size)
+    }
+
+    /// Build a table, which hashes values itself so the hasher is not used.
+    ///
+    /// The table gets a clone of the observer.
+    pub fn build_table<T, H>(&self) -> Result<ElasticHashTable<T, H, O>, BuildError>
+    where
+        H: HashCache,
+        O: ElasticObserver + Clone,
+    {
+        let size = self.table_size()?;
+        let mut table =
+            ElasticHashTable::with_params_and_observer(size, self.params, self.observer.clone());
+        if self.probe_stats {
+            table.enable_probe_stats();
+        }
+        Ok(table)
+    }
+
+    /// Build a simulator of the table, see `ElasticSimulator`
+    pub fn build_simulator(&self) -> Result<ElasticSimulator, BuildError> {
+        Ok(ElasticSimulator::with_params(
+            self.table_size()?,
+            self.params,
+        ))
+    }
+
+    pub fn build<K, V, H>(self) -> Result<ElasticHashMap<K, V, H, S, O>, BuildError>
+    where
+        K: Eq + std::hash::Hash + Clone,
+        V: Clone,
+        H: HashCache,
+        S: BuildHasher,
+        O: ElasticObserver,
+    {
+        let size = self.table_size()?;
+        let mut table =
+            ElasticHashTable::with_params_and_observer(size, self.params, self.observer);
+        if self.probe_stats {
+            table.enable_probe_stats();
+        }
+        Ok(ElasticHashMap::from_parts(table, self.hash_builder))
+    }
+}
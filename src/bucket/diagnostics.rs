@@ -0,0 +1,185 @@
+//! Renderings of the bucket layout of a table, see `ElasticHashTable::layout`
+
+use std::fmt::Write;
+
+use super::trace::SlotKind;
+
+/// Shades of the ASCII heat map, from empty to full
+const SHADES: &[u8] = b" .:-=+*#%@";
+
+/// Width of the widest bucket in an SVG image
+const SVG_WIDTH: f64 = 800.0;
+const SVG_ROW: f64 = 24.0;
+const SVG_LABEL: f64 = 60.0;
+
+/// Slots and bookkeeping of one bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BucketLayout {
+    pub size: usize,
+    /// Non-empty slots, tombstones included
+    pub load: usize,
+    pub tombstones: usize,
+    pub slots: Vec<SlotKind>,
+}
+
+impl BucketLayout {
+    /// Occupied and tombstone slots of every run of `cells` slots, for
+    /// strips narrower than the bucket
+    fn cells(&self, cells: usize) -> Vec<(usize, usize, usize)> {
+        let cells = cells.clamp(1, self.size.max(1));
+        (0..cells)
+            .map(|cell| {
+                let range = cell * self.size / cells..(cell + 1) * self.size / cells;
+                let slots = &self.slots[range];
+                let count = |kind| slots.iter().filter(|&&slot| slot == kind).count();
+                (
+                    slots.len(),
+                    count(SlotKind::Occupied),
+                    count(SlotKind::Tombstone),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Snapshot of the slots of every bucket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Layout {
+    pub buckets: Vec<BucketLayout>,
+}
+
+impl Layout {
+    /// ASCII heat map, one line per bucket.
+    ///
+    /// Every character of the strip covers about `size / width` slots of
+    /// the bucket, at least one, and is shaded by how many of them are
+    /// occupied, from `' '` to `'@'`.
+    /// Runs with more tombstones than values show as `'x'`.
+    pub fn to_ascii(&self, width: usize) -> String {
+        let mut out = String::new();
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            let strip: String = bucket
+                .cells(width)
+                .into_iter()
+                .map(|(slots, occupied, tombstones)| {
+                    if tombstones > occupied {
+                        'x'
+                    } else {
+                        let shade = occupied * (SHADES.len() - 1) / slots.max(1);
+                        SHADES[shade] as char
+                    }
+                })
+                .collect();
+            writeln!(
+                out,
+                "A_{:<3} size {:>8} load {:>8} ({:>5.1}%) tombstones {:>6} |{}|",
+                i + 1,
+                bucket.size,
+                bucket.load,
+                bucket.load as f64 * 100.0 / bucket.size.max(1) as f64,
+                bucket.tombstones,
+                strip
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    /// JSON with the counts of every bucket and its slots as a string of
+    /// `'.'` (empty), `'#'` (occupied) and `'x'` (tombstone)
+    pub fn to_json(&self) -> String {
+        let buckets: Vec<String> = self
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| {
+                let slots: String = bucket
+                    .slots
+                    .iter()
+                    .map(|kind| match kind {
+                        SlotKind::Empty => '.',
+                        SlotKind::Occupied => '#',
+                        SlotKind::Tombstone => 'x',
+                    })
+                    .collect();
+                format!(
+                    "{{\"bucket\":{},\"size\":{},\"load\":{},\"tombstones\":{},\"slots\":\"{}\"}}",
+                    i + 1,
+                    bucket.size,
+                    bucket.load,
+                    bucket.tombstones,
+                    slots
+                )
+            })
+            .collect();
+        format!("{{\"buckets\":[{}]}}", buckets.join(","))
+    }
+
+    /// SVG image with one row per bucket, as wide as the bucket relative to
+    /// the first one. Cells are shaded by occupancy, runs with more
+    /// tombstones than values are red.
+    pub fn to_svg(&self) -> String {
+        let widest = self
+            .buckets
+            .iter()
+            .map(|b| b.size)
+            .max()
+            .unwrap_or(1)
+            .max(1);
+        let height = SVG_ROW * self.buckets.len() as f64;
+        let mut out = String::new();
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="12">"#,
+            SVG_LABEL + SVG_WIDTH,
+            height
+        )
+        .unwrap();
+        for (i, bucket) in self.buckets.iter().enumerate() {
+            let y = SVG_ROW * i as f64;
+            let row_width = SVG_WIDTH * bucket.size as f64 / widest as f64;
+            writeln!(
+                out,
+                r#"<text x="0" y="{}">A_{}</text>"#,
+                y + SVG_ROW * 0.7,
+                i + 1
+            )
+            .unwrap();
+            writeln!(
+                out,
+                r##"<rect x="{}" y="{}" width="{}" height="{}" fill="#eeeeee"/>"##,
+                SVG_LABEL,
+                y + 2.0,
+                row_width,
+                SVG_ROW - 4.0
+            )
+            .unwrap();
+            // at most one cell per pixel
+            let cells = bucket.cells(row_width.ceil() as usize);
+            let cell_width = row_width / cells.len() as f64;
+            for (n, (slots, occupied, tombstones)) in cells.into_iter().enumerate() {
+                let (color, opacity) = if tombstones > occupied {
+                    ("#d62728", 1.0)
+                } else {
+                    ("#1f77b4", occupied as f64 / slots.max(1) as f64)
+                };
+                if opacity == 0.0 {
+                    continue;
+                }
+                writeln!(
+                    out,
+                    r#"<rect x="{:.2}" y="{}" width="{:.2}" height="{}" fill="{}" fill-opacity="{:.2}"/>"#,
+                    SVG_LABEL + cell_width * n as f64,
+                    y + 2.0,
+                    cell_width,
+                    SVG_ROW - 4.0,
+                    color,
+                    opacity
+                )
+                .unwrap();
+            }
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+}
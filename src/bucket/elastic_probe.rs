@@ -1,28 +1,102 @@
+use super::group::Group;
 use crate::probe;
 
+/// Probe sequence of one key in one bucket.
+///
+/// Every bucket gets its own sequence, seeded by `bucket_seed`, so where a
+/// key lands in one bucket says nothing about where it lands in another.
 pub struct ElasticProbe {
     seq: probe::ProbeSequence,
-    pos: usize,
+    attempts: u32,
 }
 
 impl ElasticProbe {
     pub fn new(seq: probe::ProbeSequence) -> Self {
-        Self { seq, pos: 0 }
+        Self { seq, attempts: 0 }
     }
 
-    pub fn probe(&mut self, i: u32, j: u32) -> usize {
-        debug_assert!(j > 0);
-        let x = super::map::ElasticHashMap::<i32, i32>::phi(i, j);
-        while self.pos < x as usize {
-            self.pos += 1;
-            self.seq.next();
+    /// Position of attempt `j` (1-based), before reduction modulo the bucket
+    /// length. Attempts must be asked for in increasing order.
+    ///
+    /// Uniform probing draws a fresh position per attempt. The other
+    /// strategies step through their sequence one group per attempt.
+    pub fn probe(&mut self, j: u32) -> usize {
+        debug_assert!(j > self.attempts);
+        while self.attempts + 1 < j {
+            self.next_position();
         }
-        self.pos += 1;
-        self.seq.next()
+        self.next_position()
     }
 
-    pub fn next_no_limit(&mut self) -> usize {
-        self.pos += 1;
-        self.seq.next_no_limit()
+    fn next_position(&mut self) -> usize {
+        self.attempts += 1;
+        match self.seq.position_at(self.attempts as usize - 1) {
+            Some(group) => group * Group::WIDTH,
+            None => self.seq.next_no_limit(),
+        }
+    }
+}
+
+/// Seed of the probe sequence of `hash` in bucket `i`.
+///
+/// Mixes the bucket index into the hash with the SplitMix64 finalizer, so the
+/// sequences of different buckets are independent.
+pub fn bucket_seed(hash: u64, i: u32) -> u64 {
+    probe::splitmix64(hash ^ (i as u64).wrapping_mul(probe::SPLITMIX_GAMMA))
+}
+
+/// Decode a probe index produced by `phi` back into `(i, j)`, or `None` if
+/// `x` is not a valid encoding
+pub fn de_phi(x: u128) -> Option<(u32, u32)> {
+    if x == 0 {
+        return None;
     }
+
+    let mut a: u32 = 0;
+    let mut b: u32 = 0;
+
+    let x_bits = 128 - x.leading_zeros() as usize;
+
+    let mut i: i32 = x_bits as i32 - 2;
+    let mut first_b = false;
+
+    while i >= 0 {
+        let bit = (x >> i) & 3;
+        if (bit >> 1) & 1 != 0 {
+            if bit & 1 == 0 && !first_b {
+                return None;
+            }
+            first_b = true;
+            b = b << 1 | (bit & 1) as u32;
+            i -= 2;
+        } else {
+            i += 1;
+            a = x as u32 & ((1 << i) - 1);
+            break;
+        }
+    }
+
+    if a == 0 || b == 0 || (a >> (i - 1)) == 0 {
+        return None;
+    }
+
+    Some((a, b))
+}
+
+/// Interleave bucket `a` and attempt `b` into a single probe index, so that
+/// all attempts of all buckets form one sequence
+pub fn phi(a: u32, b: u32) -> u128 {
+    debug_assert!(a > 0);
+    debug_assert!(b > 0);
+    let mut result: u128 = 0;
+
+    let b_bits = (32 - b.leading_zeros()) as usize + (b == 0) as usize;
+    let a_bits = (32 - a.leading_zeros()) as usize + (a == 0) as usize;
+
+    for i in (0..b_bits).rev() {
+        result = (result << 2) + 2 + ((b >> i) & 1) as u128;
+    }
+
+    result = (result << (1 + a_bits)) | a as u128;
+    result
 }
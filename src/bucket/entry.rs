@@ -0,0 +1,255 @@
+//! Entry API of `ElasticHashMap`
+
+use std::hash::Hash;
+
+use super::{
+    hash_cache::{HashCache, NoHashCache},
+    observer::{ElasticObserver, NoObserver},
+    table,
+};
+
+/// Validates the map once an entry has changed it, with the `paranoid`
+/// feature, given the slot it stored a value in
+#[cfg(feature = "paranoid")]
+pub(super) type Check<'a, K, V, H, O> =
+    Box<dyn Fn(&table::ElasticHashTable<(K, V), H, O>, Option<usize>) + 'a>;
+
+/// A view into a single entry of the map, see `ElasticHashMap::entry`
+pub enum Entry<'a, K, V, H = NoHashCache, O = NoObserver>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    Occupied(OccupiedEntry<'a, K, V, H, O>),
+    Vacant(VacantEntry<'a, K, V, H, O>),
+}
+
+impl<'a, K, V, H, O> Entry<'a, K, V, H, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        self.or_insert_with_key(|_| default())
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => {
+                let value = default(entry.key());
+                entry.insert(value)
+            }
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(ref mut entry) = self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+/// An entry holding a value
+pub struct OccupiedEntry<'a, K, V, H = NoHashCache, O = NoObserver>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub(super) inner: table::OccupiedEntry<'a, (K, V), H, O>,
+    #[cfg(feature = "paranoid")]
+    pub(super) check: Check<'a, K, V, H, O>,
+}
+
+impl<'a, K, V, H, O> OccupiedEntry<'a, K, V, H, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub fn key(&self) -> &K {
+        &self.inner.get().0
+    }
+
+    pub fn get(&self) -> &V {
+        &self.inner.get().1
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.inner.get_mut().1
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.inner.into_mut().1
+    }
+
+    /// Replace the value, returning the old one
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        #[cfg(feature = "paranoid")]
+        {
+            let (entry, table) = self.inner.remove_from_table();
+            (self.check)(table, None);
+            entry
+        }
+        #[cfg(not(feature = "paranoid"))]
+        self.inner.remove()
+    }
+}
+
+/// An entry without a value, inserting into it does not probe again
+pub struct VacantEntry<'a, K, V, H = NoHashCache, O = NoObserver>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub(super) key: K,
+    pub(super) inner: table::VacantEntry<'a, (K, V), H, O>,
+    #[cfg(feature = "paranoid")]
+    pub(super) check: Check<'a, K, V, H, O>,
+}
+
+impl<'a, K, V, H, O> VacantEntry<'a, K, V, H, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        let entry = self.inner.insert((self.key, value));
+        #[cfg(feature = "paranoid")]
+        (self.check)(entry.table(), Some(entry.index()));
+        &mut entry.into_mut().1
+    }
+}
+
+/// Like `Entry`, but looked up by a borrowed key, see `ElasticHashMap::entry_ref`.
+///
+/// The owned key is only created when a value is inserted.
+pub enum EntryRef<'a, 'b, K, Q, V, H = NoHashCache, O = NoObserver>
+where
+    K: Eq + Hash + Clone,
+    Q: ?Sized,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    Occupied(OccupiedEntry<'a, K, V, H, O>),
+    Vacant(VacantEntryRef<'a, 'b, K, Q, V, H, O>),
+}
+
+impl<'a, 'b, K, Q, V, H, O> EntryRef<'a, 'b, K, Q, V, H, O>
+where
+    K: Eq + Hash + Clone + From<&'b Q>,
+    Q: ?Sized,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let EntryRef::Occupied(ref mut entry) = self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+/// A vacant entry found by a borrowed key
+pub struct VacantEntryRef<'a, 'b, K, Q, V, H = NoHashCache, O = NoObserver>
+where
+    K: Eq + Hash + Clone,
+    Q: ?Sized,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub(super) key: &'b Q,
+    pub(super) inner: table::VacantEntry<'a, (K, V), H, O>,
+    #[cfg(feature = "paranoid")]
+    pub(super) check: Check<'a, K, V, H, O>,
+}
+
+impl<'a, 'b, K, Q, V, H, O> VacantEntryRef<'a, 'b, K, Q, V, H, O>
+where
+    K: Eq + Hash + Clone,
+    Q: ?Sized,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub fn key(&self) -> &'b Q {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V
+    where
+        K: From<&'b Q>,
+    {
+        let entry = self.inner.insert((K::from(self.key), value));
+        #[cfg(feature = "paranoid")]
+        (self.check)(entry.table(), Some(entry.index()));
+        &mut entry.into_mut().1
+    }
+}
@@ -0,0 +1,22 @@
+use std::borrow::Borrow;
+
+/// Key equivalence used by lookups.
+///
+/// Lookups accept any `Q: Equivalent<K>` with the same hash as the stored
+/// key. Every `Q` that `K` borrows as is covered by the blanket impl, other
+/// types can implement it to look up composite keys without allocating,
+/// e.g. a `(String, u32)` key by a `(&str, u32)`.
+pub trait Equivalent<K: ?Sized> {
+    /// Whether `self` equals `key`
+    fn equivalent(&self, key: &K) -> bool;
+}
+
+impl<Q, K> Equivalent<K> for Q
+where
+    Q: Eq + ?Sized,
+    K: Borrow<Q> + ?Sized,
+{
+    fn equivalent(&self, key: &K) -> bool {
+        *self == *key.borrow()
+    }
+}
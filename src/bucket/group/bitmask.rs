@@ -0,0 +1,71 @@
+/// Set of lanes of a group, as returned by the `Group::match_*` methods
+///
+/// Each lane owns `STRIDE` bits of the word and is set through its highest
+/// bit, so SSE2 masks use a stride of 1 and byte-wise masks a stride of 8.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitMask<const STRIDE: usize>(pub u64);
+
+impl<const STRIDE: usize> BitMask<STRIDE> {
+    /// The highest bit of every lane
+    const LANE_BITS: u64 = (u64::MAX / ((1 << STRIDE) - 1)) << (STRIDE - 1);
+
+    /// Mask selecting the first `n` lanes of a group
+    #[inline]
+    pub fn first_lanes(n: usize) -> Self {
+        let bits = n * STRIDE;
+        let low = if bits >= 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        };
+        BitMask(low & Self::LANE_BITS)
+    }
+
+    #[inline]
+    pub fn and(self, other: Self) -> Self {
+        BitMask(self.0 & other.0)
+    }
+
+    #[inline]
+    pub fn any_bit_set(self) -> bool {
+        self.0 != 0
+    }
+
+    #[inline]
+    pub fn lowest_set_bit(self) -> Option<usize> {
+        if self.0 == 0 {
+            None
+        } else {
+            Some(self.0.trailing_zeros() as usize / STRIDE)
+        }
+    }
+
+    #[inline]
+    fn remove_lowest_bit(self) -> Self {
+        BitMask(self.0 & self.0.wrapping_sub(1))
+    }
+}
+
+impl<const STRIDE: usize> IntoIterator for BitMask<STRIDE> {
+    type Item = usize;
+    type IntoIter = BitMaskIter<STRIDE>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        BitMaskIter(self)
+    }
+}
+
+/// Iterator over the lane indices set in a `BitMask`
+pub struct BitMaskIter<const STRIDE: usize>(BitMask<STRIDE>);
+
+impl<const STRIDE: usize> Iterator for BitMaskIter<STRIDE> {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        let bit = self.0.lowest_set_bit()?;
+        self.0 = self.0.remove_lowest_bit();
+        Some(bit)
+    }
+}
@@ -0,0 +1,45 @@
+use super::bitmask;
+
+pub type BitMask = bitmask::BitMask<8>;
+
+#[inline]
+fn repeat(byte: u8) -> u64 {
+    u64::from_ne_bytes([byte; Group::WIDTH])
+}
+
+/// Portable group of 8 control bytes packed in a `u64`
+#[derive(Clone, Copy)]
+pub struct Group(u64);
+
+impl Group {
+    pub const WIDTH: usize = 8;
+
+    /// Load the first `WIDTH` control bytes of `ctrl`
+    #[inline]
+    pub fn load(ctrl: &[u8]) -> Self {
+        let bytes: [u8; Self::WIDTH] = ctrl[..Self::WIDTH].try_into().unwrap();
+        Group(u64::from_le_bytes(bytes))
+    }
+
+    /// Lanes whose control byte may equal `byte`.
+    ///
+    /// This can report a false positive right after a real match, callers
+    /// compare the keys anyway.
+    #[inline]
+    pub fn match_byte(self, byte: u8) -> BitMask {
+        let cmp = self.0 ^ repeat(byte);
+        bitmask::BitMask(cmp.wrapping_sub(repeat(0x01)) & !cmp & repeat(0x80))
+    }
+
+    /// Lanes that are `EMPTY`, the only control byte with both top bits set
+    #[inline]
+    pub fn match_empty(self) -> BitMask {
+        bitmask::BitMask(self.0 & (self.0 << 1) & repeat(0x80))
+    }
+
+    /// Lanes that are `EMPTY` or `DELETED`
+    #[inline]
+    pub fn match_empty_or_deleted(self) -> BitMask {
+        bitmask::BitMask(self.0 & repeat(0x80))
+    }
+}
@@ -0,0 +1,80 @@
+//! Control bytes and group probing
+//!
+//! Every slot of the table has one control byte: `EMPTY`, `DELETED` or a
+//! 7-bit tag taken from the key hash. A probe loads a whole group of control
+//! bytes at once and only touches the key/value memory of lanes whose tag
+//! matches. SSE2 and NEON are used when available, other targets fall back
+//! to a portable implementation working on a `u64`.
+
+mod bitmask;
+
+#[cfg(any(
+    test,
+    not(any(
+        all(
+            target_feature = "sse2",
+            any(target_arch = "x86", target_arch = "x86_64")
+        ),
+        all(
+            target_arch = "aarch64",
+            target_feature = "neon",
+            target_endian = "little"
+        )
+    ))
+))]
+mod generic;
+#[cfg(all(
+    target_arch = "aarch64",
+    target_feature = "neon",
+    target_endian = "little"
+))]
+mod neon;
+#[cfg(all(
+    target_feature = "sse2",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+mod sse2;
+
+#[cfg(test)]
+pub use generic::Group as GenericGroup;
+#[cfg(not(any(
+    all(
+        target_feature = "sse2",
+        any(target_arch = "x86", target_arch = "x86_64")
+    ),
+    all(
+        target_arch = "aarch64",
+        target_feature = "neon",
+        target_endian = "little"
+    )
+)))]
+pub use generic::{BitMask, Group};
+#[cfg(all(
+    target_arch = "aarch64",
+    target_feature = "neon",
+    target_endian = "little"
+))]
+pub use neon::{BitMask, Group};
+#[cfg(all(
+    target_feature = "sse2",
+    any(target_arch = "x86", target_arch = "x86_64")
+))]
+pub use sse2::{BitMask, Group};
+
+/// Control byte of a slot that has never been used
+pub const EMPTY: u8 = 0b1111_1111;
+
+/// Control byte of a slot holding a tombstone
+pub const DELETED: u8 = 0b1000_0000;
+
+/// Tag stored in the control byte of an occupied slot: the top 7 bits of the hash
+#[inline]
+pub fn h2(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+/// Whether the control byte belongs to an occupied slot
+#[inline]
+pub fn is_full(ctrl: u8) -> bool {
+    ctrl & 0x80 == 0
+}
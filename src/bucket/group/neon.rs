@@ -0,0 +1,50 @@
+use core::arch::aarch64 as neon;
+
+use super::bitmask;
+
+pub type BitMask = bitmask::BitMask<8>;
+
+const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+/// Group of 8 control bytes in a NEON register
+#[derive(Clone, Copy)]
+pub struct Group(neon::uint8x8_t);
+
+impl Group {
+    pub const WIDTH: usize = 8;
+
+    /// Load the first `WIDTH` control bytes of `ctrl`
+    #[inline]
+    pub fn load(ctrl: &[u8]) -> Self {
+        let bytes = &ctrl[..Self::WIDTH];
+        // SAFETY: `bytes` is 8 bytes long
+        Group(unsafe { neon::vld1_u8(bytes.as_ptr()) })
+    }
+
+    #[inline]
+    fn to_mask(cmp: neon::uint8x8_t) -> BitMask {
+        // SAFETY: this module is only compiled when NEON is enabled
+        let word = unsafe { neon::vget_lane_u64::<0>(neon::vreinterpret_u64_u8(cmp)) };
+        bitmask::BitMask(word & HIGH_BITS)
+    }
+
+    /// Lanes whose control byte equals `byte`
+    #[inline]
+    pub fn match_byte(self, byte: u8) -> BitMask {
+        // SAFETY: this module is only compiled when NEON is enabled
+        Self::to_mask(unsafe { neon::vceq_u8(self.0, neon::vdup_n_u8(byte)) })
+    }
+
+    /// Lanes that are `EMPTY`
+    #[inline]
+    pub fn match_empty(self) -> BitMask {
+        self.match_byte(super::EMPTY)
+    }
+
+    /// Lanes that are `EMPTY` or `DELETED`, i.e. have the top bit set
+    #[inline]
+    pub fn match_empty_or_deleted(self) -> BitMask {
+        // SAFETY: this module is only compiled when NEON is enabled
+        Self::to_mask(unsafe { neon::vcltz_s8(neon::vreinterpret_s8_u8(self.0)) })
+    }
+}
@@ -0,0 +1,47 @@
+#[cfg(target_arch = "x86")]
+use core::arch::x86;
+#[cfg(target_arch = "x86_64")]
+use core::arch::x86_64 as x86;
+
+use super::bitmask;
+
+pub type BitMask = bitmask::BitMask<1>;
+
+/// Group of 16 control bytes in an SSE2 register
+#[derive(Clone, Copy)]
+pub struct Group(x86::__m128i);
+
+impl Group {
+    pub const WIDTH: usize = 16;
+
+    /// Load the first `WIDTH` control bytes of `ctrl`
+    #[inline]
+    pub fn load(ctrl: &[u8]) -> Self {
+        let bytes = &ctrl[..Self::WIDTH];
+        // SAFETY: `bytes` is 16 bytes long and the load is unaligned
+        Group(unsafe { x86::_mm_loadu_si128(bytes.as_ptr().cast()) })
+    }
+
+    /// Lanes whose control byte equals `byte`
+    #[inline]
+    pub fn match_byte(self, byte: u8) -> BitMask {
+        // SAFETY: this module is only compiled when SSE2 is enabled
+        unsafe {
+            let cmp = x86::_mm_cmpeq_epi8(self.0, x86::_mm_set1_epi8(byte as i8));
+            bitmask::BitMask(x86::_mm_movemask_epi8(cmp) as u16 as u64)
+        }
+    }
+
+    /// Lanes that are `EMPTY`
+    #[inline]
+    pub fn match_empty(self) -> BitMask {
+        self.match_byte(super::EMPTY)
+    }
+
+    /// Lanes that are `EMPTY` or `DELETED`, i.e. have the top bit set
+    #[inline]
+    pub fn match_empty_or_deleted(self) -> BitMask {
+        // SAFETY: this module is only compiled when SSE2 is enabled
+        bitmask::BitMask(unsafe { x86::_mm_movemask_epi8(self.0) } as u16 as u64)
+    }
+}
@@ -0,0 +1,65 @@
+use std::fmt::Debug;
+
+/// Per-slot storage for the full 64-bit hash of occupied entries
+///
+/// Selected through the `H` type parameter of `ElasticHashMap`. With
+/// `CachedHashes` a lookup rejects mismatching entries by hash before
+/// calling `Eq`, and rebuilding the table does not call `Hash` again.
+/// `NoHashCache` keeps nothing and costs nothing.
+pub trait HashCache: Default + Clone + Debug {
+    /// Make room for `slots` hashes, dropping the old ones
+    fn reset(&mut self, slots: usize);
+
+    /// The hash stored for slot `idx`, if hashes are cached
+    fn get(&self, idx: usize) -> Option<u64>;
+
+    /// Remember the hash of the entry stored in slot `idx`
+    fn set(&mut self, idx: usize, hash: u64);
+
+    /// Bytes allocated for the cached hashes
+    fn allocation_size(&self) -> usize {
+        0
+    }
+}
+
+/// Do not cache hashes
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoHashCache;
+
+impl HashCache for NoHashCache {
+    #[inline]
+    fn reset(&mut self, _slots: usize) {}
+
+    #[inline]
+    fn get(&self, _idx: usize) -> Option<u64> {
+        None
+    }
+
+    #[inline]
+    fn set(&mut self, _idx: usize, _hash: u64) {}
+}
+
+/// Cache the hash of every slot, 8 extra bytes per slot
+#[derive(Debug, Clone, Default)]
+pub struct CachedHashes(Vec<u64>);
+
+impl HashCache for CachedHashes {
+    fn reset(&mut self, slots: usize) {
+        self.0.clear();
+        self.0.resize(slots, 0);
+    }
+
+    #[inline]
+    fn get(&self, idx: usize) -> Option<u64> {
+        Some(self.0[idx])
+    }
+
+    #[inline]
+    fn set(&mut self, idx: usize, hash: u64) {
+        self.0[idx] = hash;
+    }
+
+    fn allocation_size(&self) -> usize {
+        self.0.capacity() * std::mem::size_of::<u64>()
+    }
+}
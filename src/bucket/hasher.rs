@@ -0,0 +1,101 @@
+//! Seeded hasher for reproducible runs, see `SeededState`
+
+use std::hash::{BuildHasher, Hasher};
+
+use crate::probe::{splitmix64, SPLITMIX_GAMMA};
+
+/// Builds `SeededHasher`s that all start from the same seed.
+///
+/// The hash of a key only depends on the seed and the bytes the key writes,
+/// never on the process or the platform's randomness. A run that hashes
+/// with a recorded seed places every key in the same slot again on targets
+/// with the same group width, 16 slots with SSE2 and 8 elsewhere; a
+/// `TraceHeader` records it. It is fast but not resistant to HashDoS, use
+/// it for tests, benchmarks and replays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SeededState {
+    seed: u64,
+}
+
+impl SeededState {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl BuildHasher for SeededState {
+    type Hasher = SeededHasher;
+
+    fn build_hasher(&self) -> SeededHasher {
+        SeededHasher {
+            state: self.seed ^ SPLITMIX_GAMMA,
+        }
+    }
+}
+
+/// Multiplicative hasher with a SplitMix64 finalizer, see `SeededState`
+#[derive(Debug, Clone)]
+pub struct SeededHasher {
+    state: u64,
+}
+
+impl SeededHasher {
+    fn mix(&mut self, word: u64) {
+        self.state = (self.state ^ word)
+            .wrapping_mul(SPLITMIX_GAMMA)
+            .rotate_left(29);
+    }
+}
+
+impl Hasher for SeededHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.mix(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        let rest = chunks.remainder();
+        if !rest.is_empty() {
+            let mut word = [0; 8];
+            word[..rest.len()].copy_from_slice(rest);
+            self.mix(u64::from_le_bytes(word));
+        }
+        // tell "ab", "c" from "a", "bc"
+        self.mix(bytes.len() as u64);
+    }
+
+    // integers are mixed as values, so the hash does not depend on the
+    // byte order of the platform
+
+    fn write_u8(&mut self, i: u8) {
+        self.mix(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.mix(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.mix(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.mix(i);
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.mix(i as u64);
+        self.mix((i >> 64) as u64);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.mix(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        splitmix64(self.state)
+    }
+}
@@ -0,0 +1,117 @@
+//! Consistency checks of an elastic hash table, see `ElasticHashTable::validate`
+
+use std::fmt;
+
+/// An internal invariant of an elastic hash table that does not hold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// The recorded load of a bucket differs from its number of non-empty
+    /// slots
+    BucketLoad {
+        bucket: usize,
+        recorded: usize,
+        actual: usize,
+    },
+    /// The recorded tombstones of a bucket differ from its tombstone slots
+    BucketTombstones {
+        bucket: usize,
+        recorded: usize,
+        actual: usize,
+    },
+    /// The table-wide tombstone count differs from the per-bucket counts
+    TombstoneCount { recorded: usize, actual: usize },
+    /// The loads do not add up to the batches done so far
+    BatchCount {
+        batch: usize,
+        recorded: usize,
+        actual: usize,
+    },
+    /// A bucket load is outside the range the current batch allows
+    BatchFill {
+        batch: usize,
+        bucket: usize,
+        load: usize,
+        min: usize,
+        max: usize,
+    },
+    /// A control byte does not match its slot
+    ControlByte { index: usize },
+    /// The cached hash of a slot is not the hash of its value
+    CachedHash { index: usize },
+    /// A lookup of a stored value does not find it
+    Unreachable { index: usize },
+    /// A lookup of a stored value finds an equal value in another slot
+    Duplicate { index: usize, other: usize },
+}
+
+impl fmt::Display for InvariantViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            InvariantViolation::BucketLoad {
+                bucket,
+                recorded,
+                actual,
+            } => write!(
+                f,
+                "A_{} records a load of {} but has {} non-empty slots",
+                bucket + 1,
+                recorded,
+                actual
+            ),
+            InvariantViolation::BucketTombstones {
+                bucket,
+                recorded,
+                actual,
+            } => write!(
+                f,
+                "A_{} records {} tombstones but has {}",
+                bucket + 1,
+                recorded,
+                actual
+            ),
+            InvariantViolation::TombstoneCount { recorded, actual } => write!(
+                f,
+                "table records {} tombstones but its buckets have {}",
+                recorded, actual
+            ),
+            InvariantViolation::BatchCount {
+                batch,
+                recorded,
+                actual,
+            } => write!(
+                f,
+                "batch {} should put the load at {} but it is {}",
+                batch, recorded, actual
+            ),
+            InvariantViolation::BatchFill {
+                batch,
+                bucket,
+                load,
+                min,
+                max,
+            } => write!(
+                f,
+                "during batch {}, A_{} has {} elements, while it should have {} to {}",
+                batch,
+                bucket + 1,
+                load,
+                min,
+                max
+            ),
+            InvariantViolation::ControlByte { index } => {
+                write!(f, "control byte of slot {} does not match it", index)
+            }
+            InvariantViolation::CachedHash { index } => {
+                write!(f, "cached hash of slot {} does not match its value", index)
+            }
+            InvariantViolation::Unreachable { index } => {
+                write!(f, "value in slot {} is not found by a lookup", index)
+            }
+            InvariantViolation::Duplicate { index, other } => {
+                write!(f, "slots {} and {} hold equal values", index, other)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InvariantViolation {}
@@ -1,622 +1,566 @@
 use std::{
-    borrow::Borrow,
     collections::hash_map::DefaultHasher,
-    hash::{Hash, Hasher},
-    ops::ControlFlow,
+    hash::{BuildHasher, BuildHasherDefault, Hash},
 };
 
-use super::{elastic_probe::ElasticProbe, utils::delta};
-use crate::probe;
+#[cfg(feature = "paranoid")]
+use super::entry::Check;
+use super::{
+    builder::BuildError,
+    diagnostics::Layout,
+    elastic_probe::{self, ElasticProbe},
+    entry::{Entry, EntryRef, OccupiedEntry, VacantEntry, VacantEntryRef},
+    equivalent::Equivalent,
+    hash_cache::{HashCache, NoHashCache},
+    invariant::InvariantViolation,
+    observer::{ElasticObserver, NoObserver},
+    stats::ProbeStats,
+    status::BatchStatus,
+    table::{self, ElasticHashTable, Search, Slot, BATCH_LANES},
+    trace::ProbeTrace,
+};
 
 // Generic KV pair
 type KVPair<K, V> = (K, V);
 
 /// Hash table element state enumeration
-#[derive(Debug, Clone)]
-pub enum EntryState<K, V>
+pub type EntryState<K, V> = Slot<KVPair<K, V>>;
+
+/// Hasher used unless another one is given, SipHash with fixed keys
+pub type DefaultHashBuilder = BuildHasherDefault<DefaultHasher>;
+
+fn make_hash<Q, S>(hash_builder: &S, key: &Q) -> u64
+where
+    Q: Hash + ?Sized,
+    S: BuildHasher,
+{
+    hash_builder.hash_one(key)
+}
+
+/// Check the invariants of `table`, whose keys hash with `hash_builder`
+fn validate_table<K, V, H, S, O>(
+    table: &ElasticHashTable<KVPair<K, V>, H, O>,
+    hash_builder: &S,
+) -> Result<(), InvariantViolation>
+where
+    K: Eq + Hash,
+    H: HashCache,
+    S: BuildHasher,
+    O: ElasticObserver,
+{
+    table.validate(make_hasher(hash_builder), |(a, _), (b, _)| a == b)
+}
+
+/// With the `paranoid` feature, panic unless `table` is intact after a
+/// mutation that stored a value in slot `stored`, if any.
+///
+/// That value is checked every time, and the whole table with
+/// `validate_table` when the table says a full check is due.
+#[cfg(feature = "paranoid")]
+fn check_table<K, V, H, S, O>(
+    table: &ElasticHashTable<KVPair<K, V>, H, O>,
+    hash_builder: &S,
+    stored: Option<usize>,
+) where
+    K: Eq + Hash,
+    H: HashCache,
+    S: BuildHasher,
+    O: ElasticObserver,
+{
+    let result = match stored {
+        _ if table.full_check_due() => validate_table(table, hash_builder),
+        Some(index) => {
+            table.validate_value(index, make_hasher(hash_builder), |(a, _), (b, _)| a == b)
+        }
+        None => Ok(()),
+    };
+    if let Err(violation) = result {
+        panic!("elastic hash map is corrupted: {}", violation);
+    }
+}
+
+/// `check_table` for an entry to run once it has changed the table
+#[cfg(feature = "paranoid")]
+fn entry_check<'a, K, V, H, S, O>(hash_builder: &'a S) -> Check<'a, K, V, H, O>
+where
+    K: Eq + Hash,
+    H: HashCache,
+    S: BuildHasher,
+    O: ElasticObserver,
+{
+    Box::new(move |table, stored| check_table(table, hash_builder, stored))
+}
+
+/// Rehash entries when the table grows
+fn make_hasher<K, V, S>(hash_builder: &S) -> impl Fn(&KVPair<K, V>) -> u64 + '_
+where
+    K: Hash,
+    S: BuildHasher,
+{
+    move |(key, _)| make_hash(hash_builder, key)
+}
+
+#[derive(Clone)]
+pub struct ElasticHashMap<K, V, H = NoHashCache, S = DefaultHashBuilder, O = NoObserver>
 where
     K: Eq + Hash + Clone,
     V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
 {
-    /// Empty slot
-    Empty,
-    /// Occupied slot
-    Occupied(KVPair<K, V>),
-    /// Tombstone marker (deleted element)
-    Tombstone,
+    pub(crate) table: ElasticHashTable<KVPair<K, V>, H, O>,
+    hash_builder: S,
 }
 
-#[derive(Debug)]
-pub struct ElasticHashMap<K, V>
+impl<K, V, H, S, O> ElasticHashMap<K, V, H, S, O>
 where
     K: Eq + Hash + Clone,
     V: Clone,
+    H: HashCache,
+    S: BuildHasher + Default,
+    O: ElasticObserver + Default,
 {
-    pub size: usize,
-    pub data: Vec<EntryState<K, V>>,
-    bucket_offsets: Vec<usize>,
-    bucket_load: Vec<usize>,
-    delta: f32,
-    max_elements: usize,
-    batch_max: Vec<usize>,
-    current_batch: (usize, usize),
-    tombstone_count: usize,
-    tombstone_bucket_map: Vec<usize>,
+    pub fn new(size: usize, delta_factor: i32) -> Self {
+        Self::from_parts(ElasticHashTable::new(size, delta_factor), S::default())
+    }
+
+    /// Create a map that holds at least `capacity` entries without growing
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self::with_capacity_and_hasher(capacity, S::default())
+    }
+
+    /// Create a map that holds at least `capacity` entries at any δ in
+    /// (0, 1) without growing
+    pub fn with_capacity_and_delta(capacity: usize, delta: f64) -> Result<Self, BuildError> {
+        let table = ElasticHashTable::with_capacity_and_delta(capacity, delta)?;
+        Ok(Self::from_parts(table, S::default()))
+    }
 }
 
-impl<K, V> ElasticHashMap<K, V>
+impl<K, V, H, S, O> ElasticHashMap<K, V, H, S, O>
 where
     K: Eq + Hash + Clone,
     V: Clone,
+    H: HashCache,
+    S: BuildHasher,
+    O: ElasticObserver + Default,
 {
-    pub fn new(size: usize, delta_factor: i32) -> Self {
-        if size == 0 {
-            panic!("Size must be greater than 0");
-        }
-        // adjust size to the nearest power of 2
-        let size = size.next_power_of_two();
-        let delta = delta(delta_factor);
-        let max_elements = (size as f32 * (1.0 - delta)) as usize;
-
-        let mut hashing = ElasticHashMap {
-            size,
-            data: Vec::with_capacity(size),
-            bucket_offsets: Vec::new(),
-            delta,
-            max_elements,
-            batch_max: Vec::new(),
-            current_batch: (0, 0),
-            bucket_load: Vec::new(),
-            tombstone_count: 0,
-            tombstone_bucket_map: Vec::new(),
-        };
-        hashing.calc_bucket_size(size);
-        for i in 0..hashing.bucket_count() {
-            hashing
-                .batch_max
-                .push(hashing.insert_batch_size(i as i32) as _);
-            hashing.tombstone_bucket_map.push(0);
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self::with_capacity_and_hasher(16, hash_builder)
+    }
+
+    pub fn with_capacity_and_hasher(capacity: usize, hash_builder: S) -> Self {
+        Self::from_parts(ElasticHashTable::with_capacity(capacity), hash_builder)
+    }
+}
+
+impl<K, V, H, S, O> ElasticHashMap<K, V, H, S, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    S: BuildHasher,
+    O: ElasticObserver,
+{
+    pub(crate) fn from_parts(table: ElasticHashTable<KVPair<K, V>, H, O>, hash_builder: S) -> Self {
+        ElasticHashMap {
+            table,
+            hash_builder,
         }
-        hashing.current_batch = (0, 0);
-        hashing
     }
 
-    pub fn with_capacity(capacity: usize) -> Self {
-        Self::new(capacity, 3)
+    pub fn hasher(&self) -> &S {
+        &self.hash_builder
+    }
+
+    /// The observer the map reports its operations to
+    pub fn observer(&self) -> &O {
+        self.table.observer()
+    }
+
+    /// Number of slots a map needs to hold `elements` entries at
+    /// δ = 1/2^`delta_factor`
+    pub fn slots_for(elements: usize, delta_factor: i32) -> usize {
+        ElasticHashTable::<KVPair<K, V>, H, O>::slots_for(elements, delta_factor)
+    }
+
+    /// Number of entries the map holds before it has to grow
+    pub fn capacity(&self) -> usize {
+        self.table.capacity()
+    }
+
+    /// Make room for at least `additional` more entries without growing
+    pub fn reserve(&mut self, additional: usize) {
+        self.table
+            .reserve(additional, make_hasher(&self.hash_builder));
+        self.paranoid_check(None);
+    }
+
+    /// Shrink the map as far as possible while it still holds
+    /// `min_capacity` entries
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        self.table
+            .shrink_to(min_capacity, make_hasher(&self.hash_builder));
+        self.paranoid_check(None);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.table.shrink_to_fit(make_hasher(&self.hash_builder));
+        self.paranoid_check(None);
     }
 
     pub fn get_underlying_size(&self) -> usize {
-        self.data.len()
+        self.table.get_underlying_size()
     }
 
-    fn hash_key<Q: ?Sized>(&self, key: &Q) -> u64
+    pub fn sequence<Q>(&self, key: &Q, i: i32) -> ElasticProbe
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Equivalent<K> + ?Sized,
     {
-        let mut hasher = DefaultHasher::new();
-        key.hash(&mut hasher);
-        hasher.finish()
+        self.table.sequence(make_hash(&self.hash_builder, key), i)
     }
 
-    pub fn sequence<Q: ?Sized>(&self, key: &Q, i: i32) -> ElasticProbe
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Equivalent<K> + ?Sized,
     {
-        debug_assert!(i > 0);
-        let hash = self.hash_key(key);
-        let seq = probe::ProbeSequence::new(
-            hash,
-            self.get_bucket(i as usize - 1).len(),
-            probe::ProbeStrategy::Uniform,
-        );
-        ElasticProbe::new(seq)
+        self.table
+            .find(make_hash(&self.hash_builder, key), |(k, _)| {
+                key.equivalent(k)
+            })
+            .map(|(_, value)| value)
     }
 
-    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    /// Look up many keys at once, see `ElasticHashTable::find_many`.
+    ///
+    /// Faster than calling `get` in a loop when the table does not fit in
+    /// cache, as the lookups wait for memory concurrently.
+    pub fn get_many<Q>(&self, keys: &[&Q]) -> Vec<Option<&V>>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Equivalent<K> + ?Sized,
     {
-        let mut probe = self.sequence(key, 1);
-        let mut k = 0;
-        let mut bucket_table = vec![false; self.bucket_count()];
-        let mut done_bucket = 0;
-        loop {
-            k += 1;
-            let pos = probe.next_no_limit();
-            if let Some((i, j)) = Self::de_phi(k - 1_u128) {
-                debug_assert_eq!(
-                    Self::phi(i, j),
-                    k - 1_u128,
-                    "phi(i,j) != k i: {}, j: {}, k: {}",
-                    i,
-                    j,
-                    k - 1
-                );
-                if i > self.bucket_count() as u32 {
-                    continue;
-                }
-                if bucket_table[i as usize - 1] {
-                    continue;
-                }
-                let bucket_idx = i as usize - 1;
-                let bucket_len = self.get_bucket(bucket_idx).len();
-                let actual_pos = pos % bucket_len;
-
-                let start = self.bucket_offsets[bucket_idx];
-                let actual_idx = start + actual_pos;
-
-                match &self.data[actual_idx] {
-                    EntryState::Occupied((ref stored_key, ref value)) => {
-                        if key.eq(stored_key.borrow()) {
-                            return Some(value);
-                        }
-
-                        if j >= bucket_len as u32 {
-                            bucket_table[bucket_idx] = true;
-                            done_bucket += 1;
-                            if done_bucket >= self.bucket_count() {
-                                return None;
-                            }
-                        }
-                    }
-                    EntryState::Empty => {
-                        bucket_table[bucket_idx] = true;
-                        done_bucket += 1;
-                        if done_bucket >= self.bucket_count() {
-                            return None;
-                        }
-                    }
-                    EntryState::Tombstone => {
-                        if j >= bucket_len as u32 {
-                            bucket_table[bucket_idx] = true;
-                            done_bucket += 1;
-                            if done_bucket >= self.bucket_count() {
-                                return None;
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        let hashes: Vec<u64> = keys
+            .iter()
+            .map(|key| make_hash(&self.hash_builder, *key))
+            .collect();
+        self.table
+            .find_many(&hashes, |n, (k, _)| keys[n].equivalent(k))
+            .into_iter()
+            .map(|pair| pair.map(|(_, value)| value))
+            .collect()
     }
 
-    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Equivalent<K> + ?Sized,
     {
-        let mut probe = self.sequence(key, 1);
-        let mut k = 0;
-        let mut bucket_table = vec![false; self.bucket_count()];
-        let mut done_bucket = 0;
-        let mut found_idx = None;
+        self.table
+            .find(make_hash(&self.hash_builder, key), |(k, _)| {
+                key.equivalent(k)
+            })
+            .map(|(k, value)| (k, value))
+    }
 
-        loop {
-            k += 1;
-            let pos = probe.next_no_limit();
-            if let Some((i, j)) = Self::de_phi(k - 1_u128) {
-                if i > self.bucket_count() as u32 {
-                    continue;
-                }
-                if bucket_table[i as usize - 1] {
-                    continue;
-                }
-
-                let bucket_idx = i as usize - 1;
-                let bucket_len = self.get_bucket(bucket_idx).len();
-                let actual_pos = pos % bucket_len;
-
-                let start = self.bucket_offsets[bucket_idx];
-                let actual_idx = start + actual_pos;
-
-                match &self.data[actual_idx] {
-                    EntryState::Occupied((ref stored_key, _)) => {
-                        if key.eq(stored_key.borrow()) {
-                            found_idx = Some(actual_idx);
-                            break;
-                        }
-
-                        if j >= bucket_len as u32 {
-                            bucket_table[bucket_idx] = true;
-                            done_bucket += 1;
-                            if done_bucket >= self.bucket_count() {
-                                return None;
-                            }
-                        }
-                    }
-                    EntryState::Empty => {
-                        bucket_table[bucket_idx] = true;
-                        done_bucket += 1;
-                        if done_bucket >= self.bucket_count() {
-                            return None;
-                        }
-                    }
-                    EntryState::Tombstone => {
-                        if j >= bucket_len as u32 {
-                            bucket_table[bucket_idx] = true;
-                            done_bucket += 1;
-                            if done_bucket >= self.bucket_count() {
-                                return None;
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.table
+            .find_mut(make_hash(&self.hash_builder, key), |(k, _)| {
+                key.equivalent(k)
+            })
+            .map(|(_, value)| value)
+    }
 
-        if let Some(idx) = found_idx {
-            if let EntryState::Occupied((_, ref mut value)) = &mut self.data[idx] {
-                return Some(value);
-            }
-        }
+    /// Get mutable references to the values of `N` keys at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any keys are overlapping.
+    pub fn get_disjoint_mut<Q, const N: usize>(&mut self, keys: [&Q; N]) -> [Option<&mut V>; N]
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hashes = keys.map(|key| make_hash(&self.hash_builder, key));
+        self.table
+            .get_disjoint_mut(hashes, |n, (k, _)| keys[n].equivalent(k))
+            .map(|pair| pair.map(|(_, value)| value))
+    }
 
-        None
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.get(key).is_some()
     }
 
-    fn insert_batch_size(&self, i: i32) -> i32 {
-        let bucket_size = self.get_bucket(i as usize).len();
-        if i == 0 {
-            (bucket_size as f32 * 0.75).ceil() as i32
-        } else {
-            let i_bucket_size = self.get_bucket(i as usize - 1).len();
-            i_bucket_size as i32
-                - (i_bucket_size as f32 * self.delta / 2.0).floor() as i32
-                - (i_bucket_size as f32 * 0.75).ceil() as i32
-                + (bucket_size as f32 * 0.75).ceil() as i32
+    /// Get the entry of `key` for in-place manipulation
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V, H, O> {
+        let hash = make_hash(&self.hash_builder, &key);
+        match self
+            .table
+            .entry(hash, |(k, _)| *k == key, make_hasher(&self.hash_builder))
+        {
+            table::Entry::Occupied(inner) => Entry::Occupied(OccupiedEntry {
+                inner,
+                #[cfg(feature = "paranoid")]
+                check: entry_check(&self.hash_builder),
+            }),
+            table::Entry::Vacant(inner) => Entry::Vacant(VacantEntry {
+                key,
+                inner,
+                #[cfg(feature = "paranoid")]
+                check: entry_check(&self.hash_builder),
+            }),
         }
     }
 
-    // tumbstone insert will always return None on success
-    fn try_tombstone(&mut self, key: K, value: V) -> Result<(), (K, V)> {
-        for (bucket_idx, tombstone_count) in self.tombstone_bucket_map.clone().iter().enumerate() {
-            if tombstone_count > &0 {
-                let mut probe = self.sequence(&key, bucket_idx as i32 + 1);
-                // has tombstone in this bucket
-                let bucket = self.get_bucket_mut(bucket_idx);
-                for j in 1..=5 {
-                    let pos = probe.probe(bucket_idx as u32 + 1, j as _);
-                    let is_tombstone = matches!(bucket[pos], EntryState::Tombstone);
-                    if is_tombstone {
-                        bucket[pos] = EntryState::Occupied((key, value));
-                        self.tombstone_bucket_map[bucket_idx] -= 1;
-                        return Ok(());
-                    }
-                }
-            }
+    /// Like `entry`, but only turns `key` into an owned `K` when inserting
+    pub fn entry_ref<'b, Q>(&mut self, key: &'b Q) -> EntryRef<'_, 'b, K, Q, V, H, O>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let hash = make_hash(&self.hash_builder, key);
+        match self.table.entry(
+            hash,
+            |(k, _)| key.equivalent(k),
+            make_hasher(&self.hash_builder),
+        ) {
+            table::Entry::Occupied(inner) => EntryRef::Occupied(OccupiedEntry {
+                inner,
+                #[cfg(feature = "paranoid")]
+                check: entry_check(&self.hash_builder),
+            }),
+            table::Entry::Vacant(inner) => EntryRef::Vacant(VacantEntryRef {
+                key,
+                inner,
+                #[cfg(feature = "paranoid")]
+                check: entry_check(&self.hash_builder),
+            }),
         }
-        Err((key, value))
     }
 
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        // check if key is already in the map
-        if let Some(v) = self.get_mut(&key) {
-            let old_value = v.clone();
-            *v = value;
-            return Some(old_value);
-        }
-
-        let re = self.try_tombstone(key.clone(), value.clone());
-        if let Ok(_) = re {
-            return None;
-        }
-        let (key, value) = re.unwrap_err();
-        let old_value = self.get(&key).cloned();
+        let hash = make_hash(&self.hash_builder, &key);
+        let search = self.table.search(hash, |(k, _)| *k == key);
+        self.insert_searched(hash, key, value, search)
+    }
 
-        if self.current_batch.1 == 0 {
-            if let ControlFlow::Break(_) = self.try_seq(key.clone(), value.clone(), 1, i32::MAX) {
-                return old_value;
+    fn insert_searched(&mut self, hash: u64, key: K, value: V, search: Search) -> Option<V> {
+        let (idx, old) = match search {
+            Search::Found(idx) => {
+                let (_, v) = self.table.slot_mut(idx);
+                (idx, Some(std::mem::replace(v, value)))
             }
-            unreachable!()
-        }
-
-        let i = self.current_batch.1;
-        let i_plus_1 = i + 1;
-        let epsilon_1 = self.epsilon(i as i32 - 1);
-        let epsilon_2 = self.epsilon(i_plus_1 as i32 - 1);
-
-        if epsilon_1 > self.delta / 2.0 && epsilon_2 > 0.25 {
-            let f_epsilon_1 = self.f::<3000>(epsilon_1);
-            if let ControlFlow::Break(_) = self.try_seq(key.clone(), value.clone(), i, f_epsilon_1)
-            {
-                return old_value;
+            Search::Absent(walks) => {
+                let idx =
+                    self.table
+                        .place(hash, (key, value), &walks, make_hasher(&self.hash_builder));
+                (idx, None)
             }
+        };
+        self.paranoid_check(Some(idx));
+        old
+    }
 
-            if let ControlFlow::Break(_) = self.try_seq(key, value, i_plus_1, i32::MAX) {
-                return old_value;
-            }
-        } else if epsilon_1 <= self.delta / 2.0 {
-            if let ControlFlow::Break(_) = self.try_seq(key, value, i_plus_1, i32::MAX) {
-                return old_value;
-            }
-        } else if epsilon_2 <= 0.25 {
-            if let ControlFlow::Break(_) = self.try_seq(key, value, i, i32::MAX) {
-                return old_value;
+    /// Insert many pairs, with the lookups of up to `BATCH_LANES` keys
+    /// interleaved as in `get_many`.
+    pub fn insert_batch<I>(&mut self, iter: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let mut iter = iter.into_iter();
+        loop {
+            let chunk: Vec<(K, V)> = iter.by_ref().take(BATCH_LANES).collect();
+            if chunk.is_empty() {
+                return;
             }
-        }
-        unreachable!()
-    }
-
-    fn try_seq(&mut self, key: K, value: V, i: usize, max_try: i32) -> ControlFlow<()> {
-        let mut probe = self.sequence(&key, i as _);
-        for j in 1..=max_try {
-            let bucket = self.get_bucket_mut(i - 1);
-            let pos = probe.probe(i as _, j as _);
-            match &bucket[pos] {
-                EntryState::Empty | EntryState::Tombstone => {
-                    let is_tombstone = matches!(bucket[pos], EntryState::Tombstone);
-                    bucket[pos] = EntryState::Occupied((key, value));
-                    self.bucket_load[i - 1] += 1;
-                    self.current_batch.0 += 1;
-                    if self.current_batch.0 >= self.batch_max[self.current_batch.1] {
-                        self.current_batch = (0, self.current_batch.1 + 1);
-                        let i = self.current_batch.1 - 1;
-                        #[cfg(any(debug_assertions, test))]
-                        {
-                            for j in 0..i {
-                                let bucket_size = self.get_bucket(j).len();
-                                let bucket_load = self.bucket_load[j];
-                                let expected = bucket_size
-                                    - (bucket_size as f32 * self.delta / 2.0).floor() as usize;
-                                assert_eq!(
-                                    expected,
-                                    bucket_load,
-                                    "when done batch {}, A_{} has {} elements, while it should have {}",
-                                    i,
-                                    j + 1,
-                                    bucket_load,
-                                    expected
-                                );
-                            }
-                        }
-                        #[cfg(any(debug_assertions, test))]
-                        {
-                            let bucket_size = self.get_bucket(i).len();
-                            let bucket_load = self.bucket_load[i];
-                            let expected = (bucket_size as f32 * 0.75).ceil() as usize;
-                            assert_eq!(
-                                expected,
-                                bucket_load,
-                                "when done batch {}, A_{} has {} elements, while it should have {}",
-                                i,
-                                i + 1,
-                                bucket_load,
-                                expected
-                            );
-                        }
-                    }
-                    if is_tombstone {
-                        self.tombstone_bucket_map[i - 1] -= 1;
-                    }
-                    return ControlFlow::Break(());
-                }
-                EntryState::Occupied((ref stored_key, _)) if stored_key == &key => {
-                    bucket[pos] = EntryState::Occupied((key, value));
-                    return ControlFlow::Break(());
-                }
-                _ => {}
+            let hashes: Vec<u64> = chunk
+                .iter()
+                .map(|(key, _)| make_hash(&self.hash_builder, key))
+                .collect();
+            let generation = self.table.generation();
+            let searches = self
+                .table
+                .search_many(&hashes, |n, (k, _)| *k == chunk[n].0);
+
+            let pairs = hashes.iter().zip(chunk).zip(searches);
+            for (n, ((hash, (key, value)), search)) in pairs.enumerate() {
+                // earlier pairs of the chunk may have taken the free slots the
+                // walk recorded, inserted the same key, or grown the table
+                let search =
+                    if self.table.is_stale(&search, generation) || hashes[..n].contains(hash) {
+                        self.table.search(*hash, |(k, _)| *k == key)
+                    } else {
+                        search
+                    };
+                self.insert_searched(*hash, key, value, search);
             }
         }
-        ControlFlow::Continue(())
     }
 
-    fn f<const C: i32>(&self, epsilon: f32) -> i32 {
-        (C as f32 * f32::min(epsilon.recip().ln().powi(2), self.delta.recip().ln())) as i32
+    /// Insert a key that is known not to be in the map, skipping the lookup.
+    ///
+    /// Inserting a key that is already present leaves the map with two
+    /// entries for it; which one lookups return is unspecified.
+    pub fn insert_unique_unchecked(&mut self, key: K, value: V) -> (&K, &mut V) {
+        let hash = make_hash(&self.hash_builder, &key);
+        let entry = self
+            .table
+            .insert_unique(hash, (key, value), make_hasher(&self.hash_builder));
+        #[cfg(feature = "paranoid")]
+        check_table(entry.table(), &self.hash_builder, Some(entry.index()));
+        let (key, value) = entry.into_mut();
+        (key, value)
     }
 
-    fn epsilon(&self, i: i32) -> f32 {
-        let bucket_size = self.get_bucket(i as usize).len();
-        let bucket_load = self.bucket_load[i as usize];
-        let load_factor = bucket_load as f32 / bucket_size as f32;
-        1.0 - load_factor
+    /// Number of slots, as `get_underlying_size`
+    pub fn size(&self) -> usize {
+        self.table.get_underlying_size()
     }
 
-    fn calc_bucket_size(&mut self, size: usize) {
-        let mut current_size = (size + 1) / 2;
-        let mut remaining_size = size;
+    /// All slots, bucket after bucket
+    pub fn data(&self) -> &[EntryState<K, V>] {
+        self.table.data()
+    }
 
-        self.bucket_offsets = Vec::new();
-        self.bucket_offsets.push(0);
+    pub fn get_bucket(&self, bucket_idx: usize) -> &[EntryState<K, V>] {
+        self.table.get_bucket(bucket_idx)
+    }
 
-        self.data = Vec::with_capacity(size);
+    pub fn bucket_count(&self) -> usize {
+        self.table.bucket_count()
+    }
 
-        while remaining_size > 0 {
-            self.data
-                .resize(self.data.len() + current_size, EntryState::Empty);
+    /// Largest probe attempt `j` an insert has used in bucket `bucket_idx`.
+    ///
+    /// Lookups never probe a bucket deeper than this.
+    pub fn probe_depth(&self, bucket_idx: usize) -> u32 {
+        self.table.probe_depth(bucket_idx)
+    }
 
-            self.bucket_offsets.push(self.data.len());
-            self.bucket_load.push(0);
-            remaining_size = remaining_size.saturating_sub(current_size);
-            current_size = (current_size + 1) / 2;
-        }
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
 
-        self.bucket_offsets.pop();
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        Q: Hash + Equivalent<K> + ?Sized,
+    {
+        let entry = self
+            .table
+            .remove(make_hash(&self.hash_builder, key), |(k, _)| {
+                key.equivalent(k)
+            });
+        self.paranoid_check(None);
+        entry
     }
 
-    pub fn get_bucket(&self, bucket_idx: usize) -> &[EntryState<K, V>] {
-        if bucket_idx >= self.bucket_offsets.len() {
-            return &[];
-        }
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.table.iter().map(|(k, v)| (k, v))
+    }
 
-        let start = self.bucket_offsets[bucket_idx];
-        let end = if bucket_idx + 1 < self.bucket_offsets.len() {
-            self.bucket_offsets[bucket_idx + 1]
-        } else {
-            self.data.len()
-        };
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&K, &mut V)> {
+        self.table.iter_mut().map(|(k, v)| (&*k, v))
+    }
 
-        &self.data[start..end]
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.table.iter().map(|(k, _)| k)
     }
 
-    pub fn get_bucket_mut(&mut self, bucket_idx: usize) -> &mut [EntryState<K, V>] {
-        if bucket_idx >= self.bucket_offsets.len() {
-            return &mut [];
-        }
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.table.iter().map(|(_, v)| v)
+    }
 
-        let start = self.bucket_offsets[bucket_idx];
-        let end = if bucket_idx + 1 < self.bucket_offsets.len() {
-            self.bucket_offsets[bucket_idx + 1]
-        } else {
-            self.data.len()
-        };
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut V> {
+        self.table.iter_mut().map(|(_, v)| v)
+    }
 
-        &mut self.data[start..end]
+    /// Bytes the map allocates, see `ElasticHashTable::allocation_size`
+    pub fn allocation_size(&self) -> usize {
+        self.table.allocation_size()
     }
 
-    pub fn bucket_count(&self) -> usize {
-        self.bucket_offsets.len()
+    /// Start recording probe-length histograms of inserts and lookups
+    pub fn enable_probe_stats(&mut self) {
+        self.table.enable_probe_stats();
     }
 
-    pub fn remove<Q: ?Sized>(&mut self, key: &Q) -> Option<V>
+    /// The probe-length histograms, if they are being recorded
+    pub fn probe_stats(&self) -> Option<&ProbeStats> {
+        self.table.probe_stats()
+    }
+
+    /// Trace the probe path of `key`: the steps of its lookup and where it
+    /// is, or where inserting it would put it
+    pub fn explain<Q>(&self, key: &Q) -> ProbeTrace
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Equivalent<K> + ?Sized,
     {
-        let mut probe = self.sequence(key, 1);
-        let mut k = 0;
-        let mut bucket_table = vec![false; self.bucket_count()];
-        let mut done_bucket = 0;
-        let mut found_location = None;
-
-        loop {
-            k += 1;
-            let pos = probe.next_no_limit();
-            if let Some((i, j)) = Self::de_phi(k - 1_u128) {
-                if i > self.bucket_count() as u32 {
-                    continue;
-                }
-                if bucket_table[i as usize - 1] {
-                    continue;
-                }
-
-                let bucket_idx = i as usize - 1;
-                let bucket_len = self.get_bucket(bucket_idx).len();
-                let actual_pos = pos % bucket_len;
-
-                let start = self.bucket_offsets[bucket_idx];
-                let actual_idx = start + actual_pos;
-
-                match &self.data[actual_idx] {
-                    EntryState::Occupied((ref stored_key, _)) => {
-                        if key.eq(stored_key.borrow()) {
-                            found_location = Some((actual_idx, bucket_idx));
-                            break;
-                        }
-
-                        if j >= bucket_len as u32 {
-                            bucket_table[bucket_idx] = true;
-                            done_bucket += 1;
-                            if done_bucket >= self.bucket_count() {
-                                return None;
-                            }
-                        }
-                    }
-                    EntryState::Empty => {
-                        bucket_table[bucket_idx] = true;
-                        done_bucket += 1;
-                        if done_bucket >= self.bucket_count() {
-                            return None;
-                        }
-                    }
-                    EntryState::Tombstone => {
-                        if j >= bucket_len as u32 {
-                            bucket_table[bucket_idx] = true;
-                            done_bucket += 1;
-                            if done_bucket >= self.bucket_count() {
-                                return None;
-                            }
-                        }
-                    }
-                }
-            }
-        }
+        self.table
+            .explain(make_hash(&self.hash_builder, key), |(k, _)| {
+                key.equivalent(k)
+            })
+    }
 
-        if let Some((idx, bucket_idx)) = found_location {
-            if let EntryState::Occupied((_, value)) =
-                std::mem::replace(&mut self.data[idx], EntryState::Tombstone)
-            {
-                self.tombstone_bucket_map[bucket_idx] += 1;
-                self.tombstone_count += 1;
-                return Some(value);
-            }
-        }
+    /// Snapshot of the slots of every bucket, renderable as text, JSON or SVG
+    pub fn layout(&self) -> Layout {
+        self.table.layout()
+    }
 
-        None
+    /// Snapshot of the batch schedule, see `BatchStatus`
+    pub fn batch_status(&self) -> BatchStatus {
+        self.table.batch_status()
     }
 
     pub fn len(&self) -> usize {
-        self.bucket_load.iter().sum::<usize>() - self.tombstone_count
+        self.table.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.len() == 0
+        self.table.is_empty()
     }
 
     pub fn clear(&mut self) {
-        for item in self.data.iter_mut() {
-            *item = EntryState::Empty;
-        }
-        for load in self.bucket_load.iter_mut() {
-            *load = 0;
-        }
-        self.current_batch = (0, 0);
-        self.tombstone_count = 0;
-        for i in self.tombstone_bucket_map.iter_mut() {
-            *i = 0;
-        }
+        self.table.clear();
+        self.paranoid_check(None);
     }
 
-    pub fn de_phi(x: u128) -> Option<(u32, u32)> {
-        if x == 0 {
-            return None;
-        }
+    /// Drop all tombstones by rebuilding the table in place.
+    ///
+    /// This also restarts the batch schedule, so the space taken by removed
+    /// entries becomes available to the elastic insertion again.
+    pub fn compact(&mut self) {
+        self.table.compact(make_hasher(&self.hash_builder));
+        self.paranoid_check(None);
+    }
 
-        let mut a: u32 = 0;
-        let mut b: u32 = 0;
-
-        let x_bits = 128 - x.leading_zeros() as usize;
-
-        let mut i: i32 = x_bits as i32 - 2;
-        let mut first_b = false;
-
-        while i >= 0 {
-            let bit = (x >> i) & 3;
-            if (bit >> 1) & 1 != 0 {
-                if bit & 1 == 0 && !first_b {
-                    return None;
-                }
-                first_b = true;
-                b = b << 1 | (bit & 1) as u32;
-                i -= 2;
-            } else {
-                i += 1;
-                a = x as u32 & ((1 << i) - 1);
-                break;
-            }
-        }
+    /// Check the internal invariants of the map, see `InvariantViolation`.
+    ///
+    /// Looks up every key, so this costs as much as a lookup per entry.
+    /// Maps built with the `paranoid` feature check the entry and the
+    /// counts a mutation touched after every mutation, run this every so
+    /// many mutations, and panic on a violation.
+    pub fn validate(&self) -> Result<(), InvariantViolation> {
+        validate_table(&self.table, &self.hash_builder)
+    }
 
-        if a == 0 || b == 0 || (a >> (i - 1)) == 0 {
-            return None;
-        }
+    /// With the `paranoid` feature, panic unless the map is intact after a
+    /// mutation that stored a value in slot `stored`, see `check_table`
+    #[allow(unused_variables)]
+    fn paranoid_check(&self, stored: Option<usize>) {
+        #[cfg(feature = "paranoid")]
+        check_table(&self.table, &self.hash_builder, stored);
+    }
 
-        Some((a, b))
+    pub fn de_phi(x: u128) -> Option<(u32, u32)> {
+        elastic_probe::de_phi(x)
     }
 
     pub fn phi(a: u32, b: u32) -> u128 {
-        debug_assert!(a > 0);
-        debug_assert!(b > 0);
-        let mut result: u128 = 0;
-
-        let b_bits = (32 - b.leading_zeros()) as usize + (b == 0) as usize;
-        let a_bits = (32 - a.leading_zeros()) as usize + (a == 0) as usize;
-
-        for i in (0..b_bits).rev() {
-            result = (result << 2) + 2 + ((b >> i) & 1) as u128;
-        }
-
-        result = (result << (1 + a_bits)) | a as u128;
-        result
+        elastic_probe::phi(a, b)
     }
 }
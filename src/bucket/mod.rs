@@ -1,12 +1,54 @@
+mod builder;
+mod diagnostics;
 mod elastic_probe;
+mod entry;
+mod equivalent;
+mod group;
+mod hash_cache;
+mod hasher;
+mod invariant;
 mod map;
+mod observer;
+mod record;
+mod simulator;
+mod stats;
+mod status;
+pub mod table;
+mod trace;
 mod traits;
 mod utils;
+mod workload;
 
-pub use map::{ElasticHashMap, EntryState};
+pub use builder::{BuildError, ElasticHashMapBuilder};
+pub use diagnostics::{BucketLayout, Layout};
+pub use entry::{Entry, EntryRef, OccupiedEntry, VacantEntry, VacantEntryRef};
+pub use equivalent::Equivalent;
+pub use hash_cache::{CachedHashes, HashCache, NoHashCache};
+pub use hasher::{SeededHasher, SeededState};
+pub use invariant::InvariantViolation;
+pub use map::{DefaultHashBuilder, ElasticHashMap, EntryState};
+#[cfg(feature = "tracing")]
+pub use observer::TracingObserver;
+pub use observer::{ElasticObserver, NoObserver, Operation};
+pub use record::{
+    read_trace, replay, HasherSeed, Recorder, ReplayError, TraceEvent, TraceHeader, TraceOp,
+    TraceTarget, TraceValue,
+};
+pub use simulator::{ElasticSimulator, Placement};
+pub use stats::{ProbeHistogram, ProbeStats};
+pub use status::{BatchStatus, BucketStatus, InsertBranch};
+pub use table::{ElasticHashTable, Slot};
+pub use trace::{AbandonReason, Phase, ProbeStep, ProbeTrace, SlotKind, TraceOutcome};
+pub use workload::{CollidingKeys, Keys, Op, OpMix, Operations, StringKeys, WorkloadRng};
 
 // Add ElasticHashing type alias for backward compatibility
 pub type ElasticHashing = ElasticHashMap<i32, i32>;
 
+#[cfg(test)]
+mod bound_test;
+#[cfg(test)]
+mod stat_test;
+#[cfg(test)]
+mod std_test;
 #[cfg(test)]
 mod test;
@@ -0,0 +1,171 @@
+//! Instrumentation hooks, see `ElasticObserver`
+
+/// Operation of the table that the events in between `on_begin` and
+/// `on_finish` belong to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    /// A search for a value, ends with `on_lookup`. A batched lookup is a
+    /// single operation with one `on_lookup` per hash.
+    Lookup,
+    /// Storing a value that was looked up before, ends with `on_insert`
+    Insert,
+    /// Removing a value that was looked up before, ends with `on_remove`
+    Remove,
+    /// Rebuilding the table with a new size, ends with `on_resize`
+    Resize,
+    /// Rebuilding the table in place, ends with `on_compact`
+    Compact,
+}
+
+impl Operation {
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Lookup => "lookup",
+            Operation::Insert => "insert",
+            Operation::Remove => "remove",
+            Operation::Resize => "resize",
+            Operation::Compact => "compact",
+        }
+    }
+}
+
+/// Callbacks on the operations of an elastic hash table.
+///
+/// The table calls these as it works, so an observer can forward them to
+/// metrics or logs. Every method does nothing by default, an observer only
+/// implements the events it needs. Bucket indices are 0-based.
+///
+/// Every event is reported between the `on_begin` and `on_finish` of the
+/// operation it is part of, on the thread running it. Operations of one
+/// thread do not nest: an insert into a full table finishes its resize
+/// before it begins. Lookups only borrow the table, so several threads may
+/// report lookups at once.
+#[allow(unused_variables)]
+pub trait ElasticObserver {
+    /// The table started `operation`
+    fn on_begin(&self, operation: Operation) {}
+
+    /// The table is done with `operation`, the last one begun
+    fn on_finish(&self, operation: Operation) {}
+
+    /// A value was stored in `bucket`, `attempts` groups were examined to
+    /// find its slot, the lookup before the insert included
+    fn on_insert(&self, bucket: usize, attempts: u32) {}
+
+    /// A lookup examined `probes` groups
+    fn on_lookup(&self, found: bool, probes: u32) {}
+
+    /// A value was removed from `bucket`, leaving a tombstone
+    fn on_remove(&self, bucket: usize) {}
+
+    /// An insert stored its value in a tombstone of `bucket`
+    fn on_tombstone_reuse(&self, bucket: usize) {}
+
+    /// The previous batch is done and `batch` started
+    fn on_batch_advance(&self, batch: usize) {}
+
+    /// The table was rebuilt from `old_slots` to `new_slots` slots
+    fn on_resize(&self, old_slots: usize, new_slots: usize) {}
+
+    /// The table was rebuilt in place, dropping `tombstones` tombstones
+    fn on_compact(&self, tombstones: usize) {}
+}
+
+/// Observer that ignores every event, it compiles down to nothing
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoObserver;
+
+impl ElasticObserver for NoObserver {}
+
+#[cfg(feature = "tracing")]
+thread_local! {
+    /// Operation spans `TracingObserver` entered on this thread, innermost
+    /// last. A span is exited on the thread that entered it, whatever other
+    /// threads do with the same map meanwhile.
+    static OPERATIONS: std::cell::RefCell<Vec<tracing::span::EnteredSpan>> =
+        const { std::cell::RefCell::new(Vec::new()) };
+}
+
+/// Observer that opens a `tracing` span for every operation of the table
+/// and emits an event for every callback inside it.
+///
+/// The operation spans are children of the span the observer was created
+/// with, so the operations of one map can be told apart, and they are
+/// entered while the operation runs, on the thread that runs it, so a
+/// subscriber can time them. Rebuilds and batch changes are logged at the
+/// debug level, everything else at the trace level.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone)]
+pub struct TracingObserver {
+    span: tracing::Span,
+}
+
+#[cfg(feature = "tracing")]
+impl TracingObserver {
+    pub fn new(span: tracing::Span) -> Self {
+        Self { span }
+    }
+
+    /// Span of the operation this thread is running
+    fn parent(&self) -> tracing::Span {
+        OPERATIONS.with_borrow(|operations| match operations.last() {
+            Some(span) => (**span).clone(),
+            None => self.span.clone(),
+        })
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Default for TracingObserver {
+    fn default() -> Self {
+        Self::new(tracing::debug_span!("elastic_hash_map"))
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl ElasticObserver for TracingObserver {
+    fn on_begin(&self, operation: Operation) {
+        let span = match operation {
+            Operation::Resize | Operation::Compact => {
+                tracing::debug_span!(parent: &self.span, "operation", name = operation.name())
+            }
+            _ => tracing::trace_span!(parent: &self.span, "operation", name = operation.name()),
+        };
+        let entered = span.entered();
+        OPERATIONS.with_borrow_mut(|operations| operations.push(entered));
+    }
+
+    fn on_finish(&self, _operation: Operation) {
+        // dropping the span exits and closes it, outside of the borrow
+        let entered = OPERATIONS.with_borrow_mut(Vec::pop);
+        drop(entered);
+    }
+
+    fn on_insert(&self, bucket: usize, attempts: u32) {
+        tracing::trace!(parent: &self.parent(), bucket, attempts, "insert");
+    }
+
+    fn on_lookup(&self, found: bool, probes: u32) {
+        tracing::trace!(parent: &self.parent(), found, probes, "lookup");
+    }
+
+    fn on_remove(&self, bucket: usize) {
+        tracing::trace!(parent: &self.parent(), bucket, "remove");
+    }
+
+    fn on_tombstone_reuse(&self, bucket: usize) {
+        tracing::trace!(parent: &self.parent(), bucket, "tombstone reuse");
+    }
+
+    fn on_batch_advance(&self, batch: usize) {
+        tracing::debug!(parent: &self.parent(), batch, "batch advance");
+    }
+
+    fn on_resize(&self, old_slots: usize, new_slots: usize) {
+        tracing::debug!(parent: &self.parent(), old_slots, new_slots, "resize");
+    }
+
+    fn on_compact(&self, tombstones: usize) {
+        tracing::debug!(parent: &self.parent(), tombstones, "compact");
+    }
+}
@@ -0,0 +1,787 @@
+//! Operation traces: record what a map does, replay it against another.
+//!
+//! A trace is JSONL, one event per line:
+//!
+//! ```text
+//! {"op":"header","seed":1,"delta":"0.125","buckets":"64,32,16,8","strategy":"uniform","prng":"lcg","group_width":16,"len":0}
+//! {"op":"insert","hash":1234,"key":"7","value":"70","result":null,"len":1}
+//! {"op":"get","hash":1234,"key":"7","result":"70","len":1}
+//! {"op":"compact","len":1}
+//! ```
+//!
+//! The first event is a `TraceHeader` with the parameters of the recording
+//! map. Keys and values are stored as strings, see `TraceValue`, and every
+//! event carries the length of the map after it. `hash` is the hash of the
+//! key under the hasher of the recording map; with a `SeededState`, the
+//! parameters of the header and the same `Group` width, feeding the hashes
+//! to `ElasticHashTable::insert_unique` or an `ElasticSimulator` places them
+//! in the same slots again.
+
+use std::{
+    collections::{hash_map::RandomState, HashMap},
+    fmt,
+    hash::{BuildHasher, BuildHasherDefault, Hash},
+    io::{self, BufRead, Write},
+};
+
+use super::{
+    group::Group,
+    hash_cache::{HashCache, NoHashCache},
+    hasher::SeededState,
+    map::{DefaultHashBuilder, ElasticHashMap},
+    observer::{ElasticObserver, NoObserver},
+};
+use crate::probe::{Prng, ProbeStrategy};
+
+/// Keys and values that can be written to a trace and read back
+pub trait TraceValue: Sized {
+    fn encode(&self) -> String;
+    fn decode(s: &str) -> Option<Self>;
+}
+
+macro_rules! trace_value_from_str {
+    ($($t:ty),*) => {
+        $(
+            impl TraceValue for $t {
+                fn encode(&self) -> String {
+                    self.to_string()
+                }
+
+                fn decode(s: &str) -> Option<Self> {
+                    s.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+trace_value_from_str!(
+    u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, bool, char, String
+);
+
+/// Hashers whose seed a trace records, so a replay can tell whether it
+/// hashes the same way.
+///
+/// Hashers without a seed of their own keep the default, other hashers can
+/// implement it with an empty body to be recorded.
+pub trait HasherSeed {
+    fn hasher_seed(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl HasherSeed for SeededState {
+    fn hasher_seed(&self) -> Option<u64> {
+        Some(self.seed())
+    }
+}
+
+impl<H> HasherSeed for BuildHasherDefault<H> {}
+
+impl HasherSeed for RandomState {}
+
+/// Parameters of the recording map, the first event of every trace.
+///
+/// Which slot a hash lands in depends on all of them, the group width
+/// included: probes walk groups of `Group::WIDTH` slots, 16 with SSE2 and 8
+/// on NEON and the generic fallback.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceHeader {
+    /// Seed of the hasher, see `HasherSeed`
+    pub seed: Option<u64>,
+    pub delta: f64,
+    /// Slots of every bucket, in order
+    pub buckets: Vec<usize>,
+    pub strategy: ProbeStrategy,
+    pub prng: Prng,
+    pub group_width: usize,
+}
+
+impl TraceHeader {
+    /// The parameters `map` has now
+    pub fn of<K, V, H, S, O>(map: &ElasticHashMap<K, V, H, S, O>) -> Self
+    where
+        K: Eq + Hash + Clone,
+        V: Clone,
+        H: HashCache,
+        S: BuildHasher + HasherSeed,
+        O: ElasticObserver,
+    {
+        let params = map.table.params();
+        TraceHeader {
+            seed: map.hasher().hasher_seed(),
+            delta: params.delta,
+            buckets: (0..map.table.bucket_count())
+                .map(|i| map.table.get_bucket(i).len())
+                .collect(),
+            strategy: params.strategy,
+            prng: params.prng,
+            group_width: Group::WIDTH,
+        }
+    }
+
+    /// Fields of the JSON object, in order
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let buckets: Vec<String> = self.buckets.iter().map(usize::to_string).collect();
+        vec![
+            (
+                "seed",
+                self.seed
+                    .map_or("null".to_string(), |seed| seed.to_string()),
+            ),
+            ("delta", json_string(&self.delta.to_string())),
+            ("buckets", json_string(&buckets.join(","))),
+            ("strategy", json_string(self.strategy.name())),
+            ("prng", json_string(self.prng.name())),
+            ("group_width", self.group_width.to_string()),
+        ]
+    }
+
+    /// The first field that differs, as it is in `self` and in `other`
+    fn difference(&self, other: &TraceHeader) -> Option<(String, String)> {
+        self.fields()
+            .into_iter()
+            .zip(other.fields())
+            .find(|(ours, theirs)| ours != theirs)
+            .map(|((name, ours), (_, theirs))| {
+                (format!("{} {}", name, ours), format!("{} {}", name, theirs))
+            })
+    }
+}
+
+/// One operation and what it returned
+#[derive(Debug, Clone, PartialEq)]
+pub enum TraceOp<K, V> {
+    Header(TraceHeader),
+    Insert { key: K, value: V, old: Option<V> },
+    Get { key: K, found: Option<V> },
+    Remove { key: K, removed: Option<V> },
+    Clear,
+    Reserve { additional: usize },
+    ShrinkToFit,
+    Compact,
+}
+
+impl<K, V> TraceOp<K, V> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TraceOp::Header(_) => "header",
+            TraceOp::Insert { .. } => "insert",
+            TraceOp::Get { .. } => "get",
+            TraceOp::Remove { .. } => "remove",
+            TraceOp::Clear => "clear",
+            TraceOp::Reserve { .. } => "reserve",
+            TraceOp::ShrinkToFit => "shrink_to_fit",
+            TraceOp::Compact => "compact",
+        }
+    }
+}
+
+/// One line of a trace
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEvent<K, V> {
+    pub op: TraceOp<K, V>,
+    /// Hash of the key, for operations that take one
+    pub hash: Option<u64>,
+    /// Length of the map after the operation
+    pub len: usize,
+}
+
+impl<K, V> TraceEvent<K, V>
+where
+    K: TraceValue,
+    V: TraceValue,
+{
+    /// The event as one line of JSON, without the line break
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![("op", json_string(self.op.name()))];
+        if let Some(hash) = self.hash {
+            fields.push(("hash", hash.to_string()));
+        }
+        let value = |value: &V| json_string(&value.encode());
+        let result = |result: &Option<V>| result.as_ref().map_or("null".to_string(), value);
+        match &self.op {
+            TraceOp::Header(header) => fields.extend(header.fields()),
+            TraceOp::Insert { key, value: v, old } => {
+                fields.push(("key", json_string(&key.encode())));
+                fields.push(("value", value(v)));
+                fields.push(("result", result(old)));
+            }
+            TraceOp::Get { key, found: res } | TraceOp::Remove { key, removed: res } => {
+                fields.push(("key", json_string(&key.encode())));
+                fields.push(("result", result(res)));
+            }
+            TraceOp::Reserve { additional } => fields.push(("additional", additional.to_string())),
+            TraceOp::Clear | TraceOp::ShrinkToFit | TraceOp::Compact => {}
+        }
+        fields.push(("len", self.len.to_string()));
+        let fields: Vec<String> = fields
+            .into_iter()
+            .map(|(name, value)| format!("\"{}\":{}", name, value))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    /// Parse one line written by `to_json`
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let fields = parse_object(line)?;
+        let field = |name: &str| {
+            fields
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("missing field {}", name))
+        };
+        let number = |name: &str| match field(name)? {
+            Json::Number(n) => Ok(*n),
+            _ => Err(format!("{} is not a number", name)),
+        };
+        let string = |name: &str| match field(name)? {
+            Json::String(s) => Ok(s.as_str()),
+            _ => Err(format!("{} is not a string", name)),
+        };
+        let invalid = |name: &str, s: &str| format!("invalid {} {:?}", name, s);
+        let decode = |name: &str, s: &str| format!("cannot decode {} {:?}", name, s);
+        let key = || match field("key")? {
+            Json::String(s) => K::decode(s).ok_or_else(|| decode("key", s)),
+            _ => Err("key is not a string".to_string()),
+        };
+        let value = |name: &str| match field(name)? {
+            Json::String(s) => V::decode(s).map(Some).ok_or_else(|| decode(name, s)),
+            Json::Null => Ok(None),
+            _ => Err(format!("{} is not a string", name)),
+        };
+
+        let op = match field("op")? {
+            Json::String(op) => match op.as_str() {
+                "header" => {
+                    let delta = string("delta")?;
+                    let strategy = string("strategy")?;
+                    let prng = string("prng")?;
+                    TraceOp::Header(TraceHeader {
+                        seed: match field("seed")? {
+                            Json::Number(seed) => Some(*seed),
+                            Json::Null => None,
+                            _ => return Err("seed is not a number".to_string()),
+                        },
+                        delta: delta.parse().map_err(|_| invalid("delta", delta))?,
+                        buckets: string("buckets")?
+                            .split(',')
+                            .filter(|size| !size.is_empty())
+                            .map(|size| size.parse().map_err(|_| invalid("bucket size", size)))
+                            .collect::<Result<_, _>>()?,
+                        strategy: ProbeStrategy::from_name(strategy)
+                            .ok_or_else(|| invalid("strategy", strategy))?,
+                        prng: Prng::from_name(prng).ok_or_else(|| invalid("prng", prng))?,
+                        group_width: number("group_width")? as usize,
+                    })
+                }
+                "insert" => TraceOp::Insert {
+                    key: key()?,
+                    value: value("value")?.ok_or("value is null")?,
+                    old: value("result")?,
+                },
+                "get" => TraceOp::Get {
+                    key: key()?,
+                    found: value("result")?,
+                },
+                "remove" => TraceOp::Remove {
+                    key: key()?,
+                    removed: value("result")?,
+                },
+                "clear" => TraceOp::Clear,
+                "reserve" => TraceOp::Reserve {
+                    additional: number("additional")? as usize,
+                },
+                "shrink_to_fit" => TraceOp::ShrinkToFit,
+                "compact" => TraceOp::Compact,
+                op => return Err(format!("unknown op {:?}", op)),
+            },
+            _ => return Err("op is not a string".to_string()),
+        };
+        Ok(TraceEvent {
+            op,
+            hash: field("hash").ok().and_then(|hash| match hash {
+                Json::Number(n) => Some(*n),
+                _ => None,
+            }),
+            len: number("len")? as usize,
+        })
+    }
+}
+
+/// Values of the flat JSON objects of a trace
+#[derive(Debug)]
+enum Json {
+    String(String),
+    Number(u64),
+    Null,
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Chars) {
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+fn expect(chars: &mut Chars, c: char) -> Result<(), String> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some(next) if next == c => Ok(()),
+        next => Err(format!("expected {:?}, found {:?}", c, next)),
+    }
+}
+
+/// The rest of a string whose opening quote was consumed
+fn parse_string(chars: &mut Chars) -> Result<String, String> {
+    let mut s = String::new();
+    loop {
+        match chars.next().ok_or("unterminated string")? {
+            '"' => return Ok(s),
+            '\\' => match chars.next().ok_or("unterminated string")? {
+                'n' => s.push('\n'),
+                'u' => {
+                    let hex: String = chars.by_ref().take(4).collect();
+                    let code = u32::from_str_radix(&hex, 16).map_err(|e| e.to_string())?;
+                    s.push(char::from_u32(code).ok_or("invalid escape")?);
+                }
+                c => s.push(c),
+            },
+            c => s.push(c),
+        }
+    }
+}
+
+/// Parse an object whose values are strings, unsigned integers or null
+fn parse_object(line: &str) -> Result<Vec<(String, Json)>, String> {
+    let mut chars = line.trim().chars().peekable();
+    let mut fields = Vec::new();
+    expect(&mut chars, '{')?;
+    loop {
+        expect(&mut chars, '"')?;
+        let name = parse_string(&mut chars)?;
+        expect(&mut chars, ':')?;
+        skip_whitespace(&mut chars);
+        let value = match chars.peek() {
+            Some('"') => {
+                chars.next();
+                Json::String(parse_string(&mut chars)?)
+            }
+            Some('n') => {
+                let null: String = chars.by_ref().take(4).collect();
+                if null != "null" {
+                    return Err(format!("unexpected {:?}", null));
+                }
+                Json::Null
+            }
+            _ => {
+                let mut digits = String::new();
+                while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                    digits.push(c);
+                }
+                Json::Number(
+                    digits
+                        .parse()
+                        .map_err(|_| format!("bad value of {}", name))?,
+                )
+            }
+        };
+        fields.push((name, value));
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            Some(',') => continue,
+            Some('}') => return Ok(fields),
+            c => return Err(format!("expected ',' or '}}', found {:?}", c)),
+        }
+    }
+}
+
+/// Read the events of a trace, one per line. Empty lines are skipped.
+pub fn read_trace<K, V>(
+    trace: impl BufRead,
+) -> impl Iterator<Item = Result<TraceEvent<K, V>, ReplayError>>
+where
+    K: TraceValue,
+    V: TraceValue,
+{
+    trace
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+        .map(|(n, line)| {
+            let line = line.map_err(ReplayError::Io)?;
+            TraceEvent::parse(&line).map_err(|message| ReplayError::Parse {
+                line: n + 1,
+                message,
+            })
+        })
+}
+
+/// Map that wraps an `ElasticHashMap` and writes every operation on it to a
+/// trace, after a `TraceHeader` with the parameters of the map.
+///
+/// Writing does not interrupt the map: the first write error is kept and
+/// returned by the next `flush`, events are dropped until then.
+pub struct Recorder<K, V, W, H = NoHashCache, S = DefaultHashBuilder, O = NoObserver>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    map: ElasticHashMap<K, V, H, S, O>,
+    out: W,
+    error: Option<io::Error>,
+}
+
+impl<K, V, W, H, S, O> Recorder<K, V, W, H, S, O>
+where
+    K: Eq + Hash + Clone + TraceValue,
+    V: Clone + TraceValue,
+    W: Write,
+    H: HashCache,
+    S: BuildHasher + HasherSeed,
+    O: ElasticObserver,
+{
+    /// Record the operations on `map` to `out`, starting with its header
+    pub fn new(map: ElasticHashMap<K, V, H, S, O>, out: W) -> Self {
+        let mut recorder = Self {
+            map,
+            out,
+            error: None,
+        };
+        let header = TraceHeader::of(&recorder.map);
+        recorder.record(TraceOp::Header(header), None);
+        recorder
+    }
+
+    pub fn map(&self) -> &ElasticHashMap<K, V, H, S, O> {
+        &self.map
+    }
+
+    fn record(&mut self, op: TraceOp<K, V>, hash: Option<u64>) {
+        write_event(&mut self.out, &mut self.error, op, hash, self.map.len());
+    }
+
+    fn hash(&self, key: &K) -> Option<u64> {
+        Some(self.map.hasher().hash_one(key))
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hash(&key);
+        let old = self.map.insert(key.clone(), value.clone());
+        self.record(
+            TraceOp::Insert {
+                key,
+                value,
+                old: old.clone(),
+            },
+            hash,
+        );
+        old
+    }
+
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let hash = self.hash(key);
+        let found = self.map.get(key);
+        let op = TraceOp::Get {
+            key: key.clone(),
+            found: found.cloned(),
+        };
+        write_event(&mut self.out, &mut self.error, op, hash, self.map.len());
+        found
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let hash = self.hash(key);
+        let removed = self.map.remove(key);
+        self.record(
+            TraceOp::Remove {
+                key: key.clone(),
+                removed: removed.clone(),
+            },
+            hash,
+        );
+        removed
+    }
+
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.record(TraceOp::Clear, None);
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.map.reserve(additional);
+        self.record(TraceOp::Reserve { additional }, None);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.map.shrink_to_fit();
+        self.record(TraceOp::ShrinkToFit, None);
+    }
+
+    pub fn compact(&mut self) {
+        self.map.compact();
+        self.record(TraceOp::Compact, None);
+    }
+
+    /// Flush the trace, or return the first error writing it
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(err) = self.error.take() {
+            return Err(err);
+        }
+        self.out.flush()
+    }
+
+    /// The map and the writer, without flushing
+    pub fn into_parts(self) -> (ElasticHashMap<K, V, H, S, O>, W) {
+        (self.map, self.out)
+    }
+}
+
+/// Write one event to `out`, unless writing failed before
+fn write_event<K, V>(
+    out: &mut impl Write,
+    error: &mut Option<io::Error>,
+    op: TraceOp<K, V>,
+    hash: Option<u64>,
+    len: usize,
+) where
+    K: TraceValue,
+    V: TraceValue,
+{
+    if error.is_none() {
+        let event = TraceEvent { op, hash, len };
+        if let Err(err) = writeln!(out, "{}", event.to_json()) {
+            *error = Some(err);
+        }
+    }
+}
+
+/// A map a trace can be replayed against
+pub trait TraceTarget<K, V> {
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+    fn get(&self, key: &K) -> Option<&V>;
+    fn remove(&mut self, key: &K) -> Option<V>;
+    fn clear(&mut self);
+    fn reserve(&mut self, additional: usize);
+    fn shrink_to_fit(&mut self);
+    /// Drop tombstones, if the map keeps any
+    fn compact(&mut self) {}
+    fn len(&self) -> usize;
+
+    /// Parameters a trace header must match, for maps whose slots depend
+    /// on them
+    fn header(&self) -> Option<TraceHeader> {
+        None
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, V, H, S, O> TraceTarget<K, V> for ElasticHashMap<K, V, H, S, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    S: BuildHasher + HasherSeed,
+    O: ElasticObserver,
+{
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        ElasticHashMap::insert(self, key, value)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        ElasticHashMap::get(self, key)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        ElasticHashMap::remove(self, key)
+    }
+
+    fn clear(&mut self) {
+        ElasticHashMap::clear(self)
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        ElasticHashMap::reserve(self, additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        ElasticHashMap::shrink_to_fit(self)
+    }
+
+    fn compact(&mut self) {
+        ElasticHashMap::compact(self)
+    }
+
+    fn len(&self) -> usize {
+        ElasticHashMap::len(self)
+    }
+
+    fn header(&self) -> Option<TraceHeader> {
+        Some(TraceHeader::of(self))
+    }
+}
+
+impl<K, V, S> TraceTarget<K, V> for HashMap<K, V, S>
+where
+    K: Eq + Hash,
+    S: BuildHasher,
+{
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        HashMap::insert(self, key, value)
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        HashMap::get(self, key)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        HashMap::remove(self, key)
+    }
+
+    fn clear(&mut self) {
+        HashMap::clear(self)
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        HashMap::reserve(self, additional)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        HashMap::shrink_to_fit(self)
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
+
+/// Why a replay stopped
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    /// Line `line` (1-based) is not a valid event
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// The target returned something else than the recorded map at event
+    /// `event` (0-based)
+    Mismatch {
+        event: usize,
+        op: &'static str,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "reading the trace: {}", err),
+            ReplayError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ReplayError::Mismatch {
+                event,
+                op,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "event {} ({}): expected {}, got {}",
+                event, op, expected, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Run every operation of `trace` on `target` and compare what it returns,
+/// and its length, with the recording. Returns the number of events.
+///
+/// A target with a `TraceTarget::header` must match the header of the
+/// trace, so every value lands in the slot it was recorded in.
+pub fn replay<K, V, M>(trace: impl BufRead, target: &mut M) -> Result<usize, ReplayError>
+where
+    K: TraceValue,
+    V: TraceValue + PartialEq,
+    M: TraceTarget<K, V> + ?Sized,
+{
+    let show =
+        |value: Option<&V>| value.map_or("None".to_string(), |v| format!("{:?}", v.encode()));
+    let mut count = 0;
+    for (n, event) in read_trace::<K, V>(trace).enumerate() {
+        let event = event?;
+        let op = event.op.name();
+        let mismatch = |expected: String, actual: String| ReplayError::Mismatch {
+            event: n,
+            op,
+            expected,
+            actual,
+        };
+        let result = match event.op {
+            TraceOp::Header(header) => target
+                .header()
+                .and_then(|actual| header.difference(&actual)),
+            TraceOp::Insert { key, value, old } => {
+                let actual = target.insert(key, value);
+                (actual != old).then(|| (show(old.as_ref()), show(actual.as_ref())))
+            }
+            TraceOp::Get { key, found } => {
+                let actual = target.get(&key);
+                (actual != found.as_ref()).then(|| (show(found.as_ref()), show(actual)))
+            }
+            TraceOp::Remove { key, removed } => {
+                let actual = target.remove(&key);
+                (actual != removed).then(|| (show(removed.as_ref()), show(actual.as_ref())))
+            }
+            TraceOp::Clear => {
+                target.clear();
+                None
+            }
+            TraceOp::Reserve { additional } => {
+                target.reserve(additional);
+                None
+            }
+            TraceOp::ShrinkToFit => {
+                target.shrink_to_fit();
+                None
+            }
+            TraceOp::Compact => {
+                target.compact();
+                None
+            }
+        };
+        if let Some((expected, actual)) = result {
+            return Err(mismatch(expected, actual));
+        }
+        if target.len() != event.len {
+            return Err(mismatch(
+                format!("len {}", event.len),
+                format!("len {}", target.len()),
+            ));
+        }
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,243 @@
+//! Occupancy-only model of the elastic insertion, see `ElasticSimulator`
+
+use super::{
+    builder::Params,
+    elastic_probe::{self, ElasticProbe},
+    group::Group,
+    stats::ProbeStats,
+    status::{BatchStatus, BucketStatus, InsertBranch},
+};
+use crate::probe;
+
+/// Where a simulated insert put its hash
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Placement {
+    /// Absolute index of the slot
+    pub index: usize,
+    /// 0-based bucket index
+    pub bucket: usize,
+    /// Attempt `j` the slot was found at
+    pub attempt: u32,
+    /// Groups examined, in the bucket it gave up on too
+    pub probes: u32,
+}
+
+/// Runs the insertion algorithm of `ElasticHashTable` over a bitset of
+/// occupied slots, without storing any values.
+///
+/// Keys are only represented by their hashes, an insert of a hash lands in
+/// the same slot as `ElasticHashTable::insert_unique` with the same
+/// parameters would put it. At one bit per slot, tables of 2^32 slots and
+/// more fit in memory, to study probe lengths before choosing δ and the
+/// bucket geometry.
+///
+/// Only inserts of distinct keys are simulated: there are no lookups,
+/// removals or tombstones, and the table does not grow. Insert probes count
+/// the groups the insertion examines, not the lookup an insert through the
+/// map starts with.
+#[derive(Debug, Clone)]
+pub struct ElasticSimulator {
+    size: usize,
+    params: Params,
+    /// Bit `idx % 64` of word `idx / 64` is set once slot `idx` is occupied
+    occupied: Vec<u64>,
+    bucket_offsets: Vec<usize>,
+    bucket_sizes: Vec<usize>,
+    bucket_load: Vec<usize>,
+    max_elements: usize,
+    batch_max: Vec<usize>,
+    current_batch: (usize, usize),
+    stats: ProbeStats,
+}
+
+impl ElasticSimulator {
+    /// Create a simulator of a table with exactly `size` slots, `params`
+    /// must be valid
+    pub(crate) fn with_params(size: usize, params: Params) -> Self {
+        let bucket_sizes = params.bucket_sizes(size);
+        let bucket_offsets = bucket_sizes
+            .iter()
+            .scan(0, |offset, &bucket_size| {
+                let start = *offset;
+                *offset += bucket_size;
+                Some(start)
+            })
+            .collect();
+        ElasticSimulator {
+            size,
+            params,
+            occupied: vec![0; size.div_ceil(64)],
+            bucket_offsets,
+            bucket_load: vec![0; bucket_sizes.len()],
+            max_elements: (size as f64 * (1.0 - params.delta)) as usize,
+            batch_max: params.batch_sizes(&bucket_sizes),
+            current_batch: (0, 0),
+            stats: ProbeStats::new(bucket_sizes.len()),
+            bucket_sizes,
+        }
+    }
+
+    /// Number of slots
+    pub fn slots(&self) -> usize {
+        self.size
+    }
+
+    /// δ, the fraction of slots left free once the table is full
+    pub fn delta(&self) -> f64 {
+        self.params.delta
+    }
+
+    /// Number of hashes the table holds, `1 - δ` of its slots
+    pub fn capacity(&self) -> usize {
+        self.max_elements
+    }
+
+    pub fn len(&self) -> usize {
+        self.bucket_load.iter().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn bucket_count(&self) -> usize {
+        self.bucket_sizes.len()
+    }
+
+    /// Number of slots of bucket `bucket_idx`
+    pub fn bucket_size(&self, bucket_idx: usize) -> usize {
+        self.bucket_sizes[bucket_idx]
+    }
+
+    /// Number of occupied slots of bucket `bucket_idx`
+    pub fn bucket_load(&self, bucket_idx: usize) -> usize {
+        self.bucket_load[bucket_idx]
+    }
+
+    pub fn is_occupied(&self, idx: usize) -> bool {
+        self.occupied[idx / 64] >> (idx % 64) & 1 == 1
+    }
+
+    /// Probe lengths of the inserts so far, by the bucket the hash landed
+    /// in. Lookups are not simulated, so hits and misses stay empty.
+    pub fn probe_stats(&self) -> &ProbeStats {
+        &self.stats
+    }
+
+    /// Snapshot of the batch schedule, as `ElasticHashTable::batch_status`
+    pub fn batch_status(&self) -> BatchStatus {
+        BatchStatus {
+            batch: self.current_batch.1,
+            batch_count: self.current_batch.0,
+            batch_max: self.batch_max.clone(),
+            buckets: (0..self.bucket_count())
+                .map(|i| BucketStatus {
+                    load: self.bucket_load[i],
+                    capacity: self.bucket_sizes[i],
+                    tombstones: 0,
+                    epsilon: 1.0 - self.bucket_load[i] as f64 / self.bucket_sizes[i] as f64,
+                })
+                .collect(),
+            next_insert: self.insert_branch(),
+        }
+    }
+
+    /// Insert a key with hash `hash`, `None` once the table is full
+    pub fn insert(&mut self, hash: u64) -> Option<Placement> {
+        let mut probes = 0;
+        let (bucket_idx, idx, j) = match self.insert_branch() {
+            InsertBranch::Grow => return None,
+            InsertBranch::Limited { bucket, limit } => self
+                .free_slot(hash, bucket, limit, &mut probes)
+                .map(|(idx, j)| (bucket, idx, j))
+                .or_else(|| {
+                    self.free_slot(hash, bucket + 1, u32::MAX, &mut probes)
+                        .map(|(idx, j)| (bucket + 1, idx, j))
+                }),
+            InsertBranch::Next { bucket } | InsertBranch::Only { bucket } => self
+                .free_slot(hash, bucket, u32::MAX, &mut probes)
+                .map(|(idx, j)| (bucket, idx, j)),
+        }
+        .expect("the batch schedule left a bucket without free slots");
+        self.occupy(bucket_idx, idx);
+        self.stats.record_insert(bucket_idx, probes);
+        Some(Placement {
+            index: idx,
+            bucket: bucket_idx,
+            attempt: j,
+            probes,
+        })
+    }
+
+    /// Insert every hash of `hashes` until the table is full, returns how
+    /// many were inserted
+    pub fn fill(&mut self, hashes: impl IntoIterator<Item = u64>) -> usize {
+        hashes
+            .into_iter()
+            .take_while(|&hash| self.insert(hash).is_some())
+            .count()
+    }
+
+    fn insert_branch(&self) -> InsertBranch {
+        if self.len() >= self.max_elements {
+            return InsertBranch::Grow;
+        }
+        InsertBranch::choose(
+            &self.params,
+            self.current_batch.1,
+            &self.bucket_load,
+            |bucket_idx| self.bucket_sizes[bucket_idx],
+        )
+    }
+
+    /// First free slot of bucket `bucket_idx` within `max_try` attempts and
+    /// the attempt it is found at, probing the groups the table would
+    fn free_slot(
+        &self,
+        hash: u64,
+        bucket_idx: usize,
+        max_try: u32,
+        probes: &mut u32,
+    ) -> Option<(usize, u32)> {
+        let bucket_len = self.bucket_sizes[bucket_idx];
+        let mut probe = ElasticProbe::new(probe::ProbeSequence::with_prng(
+            elastic_probe::bucket_seed(hash, bucket_idx as u32 + 1),
+            bucket_len,
+            self.params.strategy,
+            self.params.prng,
+        ));
+        for j in 1..=max_try {
+            *probes += 1;
+            let group_start = probe.probe(j) % bucket_len / Group::WIDTH * Group::WIDTH;
+            let base = self.bucket_offsets[bucket_idx] + group_start;
+            let lanes = Group::WIDTH.min(bucket_len - group_start);
+            if let Some(lane) = self.first_free(base, lanes) {
+                return Some((base + lane, j));
+            }
+        }
+        None
+    }
+
+    /// Lowest free slot of the `lanes` slots from `base`, relative to it
+    fn first_free(&self, base: usize, lanes: usize) -> Option<usize> {
+        let (word, shift) = (base / 64, base % 64);
+        let mut bits = self.occupied[word] >> shift;
+        if shift > 0 {
+            if let Some(next) = self.occupied.get(word + 1) {
+                bits |= next << (64 - shift);
+            }
+        }
+        let lane = (!bits).trailing_zeros() as usize;
+        (lane < lanes).then_some(lane)
+    }
+
+    fn occupy(&mut self, bucket_idx: usize, idx: usize) {
+        debug_assert!(!self.is_occupied(idx));
+        self.occupied[idx / 64] |= 1 << (idx % 64);
+        self.bucket_load[bucket_idx] += 1;
+        self.current_batch.0 += 1;
+        if self.current_batch.0 >= self.batch_max[self.current_batch.1] {
+            self.current_batch = (0, self.current_batch.1 + 1);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! Statistical quality of the uniform probe sequences.
+//!
+//! The sequences are seeded with sequential integers, the worst case for a
+//! generator that does not mix its seed. Thresholds are critical values at
+//! p = 0.001, or several standard deviations for the other statistics.
+
+use crate::probe::{Prng, ProbeSequence, ProbeStrategy};
+
+const GENERATORS: [Prng; 3] = [Prng::SplitMix64, Prng::Pcg, Prng::XorShiftStar];
+
+fn sequence(key: u64, len: usize, prng: Prng) -> ProbeSequence {
+    ProbeSequence::with_prng(key, len, ProbeStrategy::Uniform, prng)
+}
+
+/// Chi-square statistic of `counts` against a uniform distribution
+fn chi_square(counts: &[usize]) -> f64 {
+    let total: usize = counts.iter().sum();
+    let expected = total as f64 / counts.len() as f64;
+    counts
+        .iter()
+        .map(|&c| (c as f64 - expected).powi(2) / expected)
+        .sum()
+}
+
+/// Pearson correlation coefficient of `xs` and `ys`
+fn correlation(xs: &[f64], ys: &[f64]) -> f64 {
+    let n = xs.len() as f64;
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = ys.iter().sum::<f64>() / n;
+    let mut cov = 0.0;
+    let mut var_x = 0.0;
+    let mut var_y = 0.0;
+    for (x, y) in xs.iter().zip(ys) {
+        cov += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    cov / (var_x * var_y).sqrt()
+}
+
+/// First `probes` positions of every key in `keys`, modulo `len`
+fn positions(keys: std::ops::Range<u64>, len: usize, probes: usize, prng: Prng) -> Vec<Vec<usize>> {
+    keys.map(|key| {
+        let mut seq = sequence(key, len, prng);
+        (0..probes).map(|_| seq.next()).collect()
+    })
+    .collect()
+}
+
+fn uniformity(prng: Prng) -> f64 {
+    const LEN: usize = 1024;
+    const BINS: usize = 64;
+    let mut counts = [0usize; BINS];
+    for probes in positions(0..BINS as u64 * 100, LEN, 4, prng) {
+        for pos in probes {
+            counts[pos * BINS / LEN] += 1;
+        }
+    }
+    chi_square(&counts)
+}
+
+fn distinct_rate(prng: Prng) -> f64 {
+    const LEN: usize = 256;
+    let keys = positions(0..200, LEN, LEN, prng);
+    let mut distinct = 0;
+    for probes in &keys {
+        let mut seen = [false; LEN];
+        for &pos in probes {
+            distinct += !std::mem::replace(&mut seen[pos], true) as usize;
+        }
+    }
+    distinct as f64 / (keys.len() * LEN) as f64
+}
+
+fn consecutive_correlation(prng: Prng) -> f64 {
+    let first: Vec<f64> = positions(0..10000, 1 << 20, 1, prng)
+        .iter()
+        .map(|probes| probes[0] as f64)
+        .collect();
+    correlation(&first[..first.len() - 1], &first[1..])
+}
+
+#[test]
+fn test_chi_square_uniformity() {
+    for prng in GENERATORS {
+        // 63 degrees of freedom
+        let chi = uniformity(prng);
+        assert!(chi < 103.4, "{:?}: chi-square {}", prng, chi);
+    }
+}
+
+#[test]
+fn test_repeat_rate() {
+    // `len` uniform draws from `len` slots hit 1 - (1 - 1/len)^len ≈ 1 - 1/e of them
+    let expected = 1.0 - (1.0 - 1.0 / 256.0f64).powi(256);
+    for prng in GENERATORS {
+        let rate = distinct_rate(prng);
+        assert!(
+            (rate - expected).abs() < 0.01,
+            "{:?}: distinct rate {}",
+            prng,
+            rate
+        );
+    }
+}
+
+#[test]
+fn test_consecutive_key_correlation() {
+    for prng in GENERATORS {
+        let r = consecutive_correlation(prng);
+        assert!(r.abs() < 0.04, "{:?}: correlation {}", prng, r);
+    }
+}
+
+#[test]
+fn test_lcg_small_seeds() {
+    // sequential seeds step the first LCG output by a constant, which is why
+    // the other generators are offered
+    let r = consecutive_correlation(Prng::Lcg);
+    assert!(r > 0.2, "correlation {}", r);
+}
+
+#[test]
+fn test_prng_maps() {
+    for prng in [Prng::Lcg, Prng::SplitMix64, Prng::Pcg, Prng::XorShiftStar] {
+        let mut map: super::ElasticHashing = super::ElasticHashMapBuilder::new()
+            .capacity(1000)
+            .prng(prng)
+            .build()
+            .unwrap();
+        for i in 0..1000 {
+            map.insert(i, i);
+        }
+        assert!((0..1000).all(|i| map.get(&i) == Some(&i)), "{:?}", prng);
+    }
+}
+
+#[test]
+fn test_default_prng() {
+    // probe sequences seeded before the generators became pluggable must not
+    // change, so the LCG stays the default
+    assert_eq!(Prng::default(), Prng::Lcg);
+    for key in [0, 1, 42, u64::MAX] {
+        let mut default = ProbeSequence::new(key, 1024, ProbeStrategy::Uniform);
+        let mut lcg = sequence(key, 1024, Prng::Lcg);
+        assert!((0..64).all(|_| default.next() == lcg.next()), "{}", key);
+    }
+}
@@ -0,0 +1,231 @@
+//! Probe-length histograms, see `ElasticHashTable::enable_probe_stats`
+
+use std::{
+    fmt::Write,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Bin 0 counts zero-length probes, bin `k` the lengths in `[2^(k-1), 2^k)`
+const BINS: usize = 65;
+
+/// Log-bucketed histogram of probe lengths.
+///
+/// Recording only needs a shared reference, so lookups can record too.
+#[derive(Debug)]
+pub struct ProbeHistogram {
+    bins: [AtomicU64; BINS],
+    count: AtomicU64,
+    sum: AtomicU64,
+    max: AtomicU64,
+}
+
+impl ProbeHistogram {
+    pub fn new() -> Self {
+        Self {
+            bins: [const { AtomicU64::new(0) }; BINS],
+            count: AtomicU64::new(0),
+            sum: AtomicU64::new(0),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    /// Record one operation that examined `probes` groups
+    pub fn record(&self, probes: u64) {
+        let bin = (u64::BITS - probes.leading_zeros()) as usize;
+        self.bins[bin].fetch_add(1, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum.fetch_add(probes, Ordering::Relaxed);
+        self.max.fetch_max(probes, Ordering::Relaxed);
+    }
+
+    /// Add the operations recorded by `other`
+    pub fn merge(&self, other: &ProbeHistogram) {
+        for (bin, other) in self.bins.iter().zip(&other.bins) {
+            bin.fetch_add(other.load(Ordering::Relaxed), Ordering::Relaxed);
+        }
+        self.count.fetch_add(other.count(), Ordering::Relaxed);
+        self.sum
+            .fetch_add(other.sum.load(Ordering::Relaxed), Ordering::Relaxed);
+        self.max.fetch_max(other.max(), Ordering::Relaxed);
+    }
+
+    pub fn reset(&self) {
+        for bin in &self.bins {
+            bin.store(0, Ordering::Relaxed);
+        }
+        self.count.store(0, Ordering::Relaxed);
+        self.sum.store(0, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+    }
+
+    /// Number of recorded operations
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Total of the recorded probe lengths
+    pub fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+
+    /// Mean probe length, 0 when nothing was recorded
+    pub fn mean(&self) -> f64 {
+        match self.count() {
+            0 => 0.0,
+            count => self.sum() as f64 / count as f64,
+        }
+    }
+
+    /// Longest recorded probe length
+    pub fn max(&self) -> u64 {
+        self.max.load(Ordering::Relaxed)
+    }
+
+    /// Upper bound of the probe length of the `p` quantile, `p` in [0, 1].
+    ///
+    /// Exact up to the bin width: the largest length of the bin the quantile
+    /// falls into, but never more than `max`.
+    pub fn percentile(&self, p: f64) -> u64 {
+        let rank = (p.clamp(0.0, 1.0) * self.count() as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bin, range) in self.bins() {
+            seen += bin;
+            if seen >= rank {
+                return range.1.min(self.max());
+            }
+        }
+        0
+    }
+
+    /// Non-empty bins as `(count, (lowest, highest length))`
+    pub fn bins(&self) -> impl Iterator<Item = (u64, (u64, u64))> + '_ {
+        self.bins.iter().enumerate().filter_map(|(k, bin)| {
+            let count = bin.load(Ordering::Relaxed);
+            let range = match k {
+                0 => (0, 0),
+                k => (1 << (k - 1), u64::MAX >> (u64::BITS as usize - k)),
+            };
+            (count > 0).then_some((count, range))
+        })
+    }
+
+    /// The histogram as a JSON object with its count, mean, p50, p99, max
+    /// and non-empty bins
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        write!(
+            json,
+            "{{\"count\":{},\"mean\":{},\"p50\":{},\"p99\":{},\"max\":{},\"bins\":[",
+            self.count(),
+            self.mean(),
+            self.percentile(0.5),
+            self.percentile(0.99),
+            self.max()
+        )
+        .unwrap();
+        for (n, (count, (lo, hi))) in self.bins().enumerate() {
+            if n > 0 {
+                json.push(',');
+            }
+            write!(json, "{{\"lo\":{},\"hi\":{},\"count\":{}}}", lo, hi, count).unwrap();
+        }
+        json.push_str("]}");
+        json
+    }
+}
+
+impl Default for ProbeHistogram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clone for ProbeHistogram {
+    fn clone(&self) -> Self {
+        let clone = Self::new();
+        clone.merge(self);
+        clone
+    }
+}
+
+/// Probe lengths of the operations on a table, counted in groups examined.
+///
+/// An insert counts the lookup it starts with plus the probes that place
+/// the value, and is recorded under the bucket the value lands in.
+/// Lookups count every search, including the one of an insert.
+#[derive(Debug, Clone, Default)]
+pub struct ProbeStats {
+    inserts: Vec<ProbeHistogram>,
+    hits: ProbeHistogram,
+    misses: ProbeHistogram,
+}
+
+impl ProbeStats {
+    pub(crate) fn new(bucket_count: usize) -> Self {
+        let mut stats = Self::default();
+        stats.track_buckets(bucket_count);
+        stats
+    }
+
+    /// Keep a histogram for at least `bucket_count` buckets
+    pub(crate) fn track_buckets(&mut self, bucket_count: usize) {
+        if self.inserts.len() < bucket_count {
+            self.inserts.resize_with(bucket_count, ProbeHistogram::new);
+        }
+    }
+
+    pub(crate) fn record_insert(&self, bucket_idx: usize, probes: u32) {
+        self.inserts[bucket_idx].record(probes as u64);
+    }
+
+    pub(crate) fn record_lookup(&self, found: bool, probes: u32) {
+        match found {
+            true => self.hits.record(probes as u64),
+            false => self.misses.record(probes as u64),
+        }
+    }
+
+    /// Inserts by the bucket the value landed in
+    pub fn inserts(&self) -> &[ProbeHistogram] {
+        &self.inserts
+    }
+
+    /// Inserts over all buckets
+    pub fn insert_total(&self) -> ProbeHistogram {
+        let total = ProbeHistogram::new();
+        for bucket in &self.inserts {
+            total.merge(bucket);
+        }
+        total
+    }
+
+    /// Successful lookups
+    pub fn hits(&self) -> &ProbeHistogram {
+        &self.hits
+    }
+
+    /// Unsuccessful lookups
+    pub fn misses(&self) -> &ProbeHistogram {
+        &self.misses
+    }
+
+    pub fn reset(&self) {
+        for bucket in &self.inserts {
+            bucket.reset();
+        }
+        self.hits.reset();
+        self.misses.reset();
+    }
+
+    /// All histograms as one JSON object
+    pub fn to_json(&self) -> String {
+        let buckets: Vec<String> = self.inserts.iter().map(ProbeHistogram::to_json).collect();
+        format!(
+            "{{\"inserts\":{{\"total\":{},\"buckets\":[{}]}},\"hits\":{},\"misses\":{}}}",
+            self.insert_total().to_json(),
+            buckets.join(","),
+            self.hits.to_json(),
+            self.misses.to_json()
+        )
+    }
+}
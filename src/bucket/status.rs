@@ -0,0 +1,83 @@
+//! Read-only view of the batch schedule, see `ElasticHashTable::batch_status`
+
+use super::builder::Params;
+
+/// Where the next insert into an empty slot goes.
+///
+/// Bucket indices are 0-based, as in `get_bucket`. An insert may reuse a
+/// tombstone its lookup came across instead, wherever that is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertBranch {
+    /// Probe `bucket` for at most `limit` attempts, `f(ε)` of its free
+    /// fraction, then fall back to `bucket + 1`
+    Limited { bucket: usize, limit: u32 },
+    /// `bucket - 1` has reached its final load, probe `bucket` only
+    Next { bucket: usize },
+    /// Probe `bucket` only: the first batch, the last batch, or the next
+    /// bucket has reached the load of its first batch
+    Only { bucket: usize },
+    /// The table is full and grows first
+    Grow,
+}
+
+impl InsertBranch {
+    /// Branch of the batch schedule in batch `batch` of a table that is not
+    /// full, whose buckets hold `loads` values, bucket `i` in `size(i)` slots
+    pub(crate) fn choose(
+        params: &Params,
+        batch: usize,
+        loads: &[usize],
+        size: impl Fn(usize) -> usize,
+    ) -> Self {
+        let i = batch;
+        if i == 0 {
+            return InsertBranch::Only { bucket: 0 };
+        }
+        if i == loads.len() {
+            // the last batch has no next bucket to spill into
+            return InsertBranch::Only { bucket: i - 1 };
+        }
+        // ε(A_i) > δ/2 and ε(A_{i+1}) > 1 - batch_fill, compared on loads so
+        // that rounding cannot disagree with the batch sizes
+        let room_1 = loads[i - 1] < params.full_target(size(i - 1));
+        let room_2 = loads[i] < params.fill_target(size(i));
+
+        if room_1 && room_2 {
+            let epsilon = 1.0 - loads[i - 1] as f64 / size(i - 1) as f64;
+            InsertBranch::Limited {
+                bucket: i - 1,
+                limit: params.probe_limit(epsilon).max(0) as u32,
+            }
+        } else if !room_1 {
+            InsertBranch::Next { bucket: i }
+        } else {
+            InsertBranch::Only { bucket: i - 1 }
+        }
+    }
+}
+
+/// Load of one bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BucketStatus {
+    /// Non-empty slots, tombstones included
+    pub load: usize,
+    /// Slots of the bucket
+    pub capacity: usize,
+    pub tombstones: usize,
+    /// Free fraction of the bucket, `1 - load / capacity`
+    pub epsilon: f64,
+}
+
+/// Snapshot of the elastic insertion state of a table
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchStatus {
+    /// Index of the running batch
+    pub batch: usize,
+    /// Empty slots filled so far in the running batch
+    pub batch_count: usize,
+    /// Number of empty slots each batch fills, the last batch fills the last
+    /// bucket
+    pub batch_max: Vec<usize>,
+    pub buckets: Vec<BucketStatus>,
+    pub next_insert: InsertBranch,
+}
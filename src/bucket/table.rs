@@ -0,0 +1,1592 @@
+//! Low-level elastic hash table
+//!
+//! `ElasticHashTable<T>` stores arbitrary values in the elastic bucket
+//! layout. It never hashes or compares values itself: every operation takes
+//! the hash and an equality closure from the caller, so the key may be
+//! derived from the value. `ElasticHashMap` is built on top of it.
+
+use std::ops::ControlFlow;
+
+use super::{
+    builder::{BuildError, Params},
+    diagnostics::{BucketLayout, Layout},
+    elastic_probe::{self, ElasticProbe},
+    group::{self, BitMask, Group},
+    hash_cache::{HashCache, NoHashCache},
+    invariant::InvariantViolation,
+    observer::{ElasticObserver, NoObserver, Operation},
+    stats::ProbeStats,
+    status::{BatchStatus, BucketStatus, InsertBranch},
+    trace::{AbandonReason, Phase, ProbeStep, ProbeTrace, SlotKind, TraceOutcome},
+    utils::{delta, prefetch, slots_for},
+};
+use crate::probe;
+
+/// Hash table element state enumeration
+#[derive(Debug, Clone)]
+pub enum Slot<T> {
+    /// Empty slot
+    Empty,
+    /// Occupied slot
+    Occupied(T),
+    /// Tombstone marker (deleted element)
+    Tombstone,
+}
+
+/// Per-bucket progress of a probe walk
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct BucketWalk {
+    /// No more slots of this bucket need to be examined
+    done: bool,
+    /// First free slot (absolute index) seen in this bucket and the attempt `j` it was found at
+    free: Option<(usize, u32)>,
+    /// Last attempt `j` examined in this bucket
+    last_j: u32,
+}
+
+/// An in-progress walk over the probe sequence of one key, see `search`
+struct Walk {
+    /// Probe sequence of every bucket, created on the first visit
+    probes: Vec<Option<ElasticProbe>>,
+    hash: u64,
+    k: u128,
+    buckets: Vec<BucketWalk>,
+    done_bucket: usize,
+    /// Groups examined so far
+    examined: u32,
+    /// Steps taken so far, when tracing for `explain`
+    trace: Option<Vec<ProbeStep>>,
+    /// Group to examine next: bucket index, attempt `j` and probe position
+    next: Option<(usize, u32, usize)>,
+}
+
+impl Walk {
+    /// Abandon a bucket, returns whether every bucket is abandoned now
+    fn abandon(&mut self, bucket_idx: usize) -> bool {
+        self.buckets[bucket_idx].done = true;
+        self.done_bucket += 1;
+        self.done_bucket >= self.buckets.len()
+    }
+
+    fn absent(&mut self) -> Search {
+        Search::Absent(std::mem::take(&mut self.buckets))
+    }
+}
+
+/// Number of keys whose probe walks advance in lockstep in batched operations
+pub(crate) const BATCH_LANES: usize = 8;
+
+/// Fewest mutations between two full paranoid checks, see `full_check_due`
+#[cfg(feature = "paranoid")]
+const PARANOID_INTERVAL: u64 = 64;
+
+/// Result of walking the probe sequence of a key
+pub(crate) enum Search {
+    /// The key is stored at this absolute index
+    Found(usize),
+    /// The key is absent, with the walk state of every bucket
+    Absent(Vec<BucketWalk>),
+}
+
+#[derive(Debug, Clone)]
+pub struct ElasticHashTable<T, H = NoHashCache, O = NoObserver>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    size: usize,
+    data: Vec<Slot<T>>,
+    /// One control byte per slot, followed by `Group::WIDTH` padding bytes
+    ctrl: Vec<u8>,
+    /// Hash of every occupied slot, when `H` caches them
+    hashes: H,
+    bucket_offsets: Vec<usize>,
+    bucket_load: Vec<usize>,
+    params: Params,
+    max_elements: usize,
+    batch_max: Vec<usize>,
+    current_batch: (usize, usize),
+    tombstone_count: usize,
+    tombstone_bucket_map: Vec<usize>,
+    /// Largest attempt `j` any insert has used, per bucket
+    bucket_depth: Vec<u32>,
+    /// Bumped whenever the slots are laid out anew, so that a walk taken
+    /// before can tell it no longer applies
+    generation: u64,
+    /// Mutations so far, see `full_check_due`
+    #[cfg(feature = "paranoid")]
+    mutations: u64,
+    /// Probe-length histograms, once enabled
+    stats: Option<Box<ProbeStats>>,
+    observer: O,
+}
+
+impl<T, H, O> ElasticHashTable<T, H, O>
+where
+    H: HashCache,
+    O: ElasticObserver + Default,
+{
+    pub fn new(size: usize, delta_factor: i32) -> Self {
+        if size == 0 {
+            panic!("Size must be greater than 0");
+        }
+        let params = Params {
+            delta: delta(delta_factor),
+            ..Params::default()
+        };
+        // adjust size to the nearest power of 2
+        Self::with_params(size.next_power_of_two(), params)
+    }
+
+    /// Create a table with exactly `size` slots, `params` must be valid
+    pub(crate) fn with_params(size: usize, params: Params) -> Self {
+        Self::with_params_and_observer(size, params, O::default())
+    }
+
+    /// Create a table that holds at least `capacity` values at δ = 1/8
+    /// without growing
+    pub fn with_capacity(capacity: usize) -> Self {
+        let params = Params::default();
+        Self::with_params(params.slots_for(capacity), params)
+    }
+
+    /// Create a table that holds at least `capacity` values at any δ in
+    /// (0, 1) without growing
+    pub fn with_capacity_and_delta(capacity: usize, delta: f64) -> Result<Self, BuildError> {
+        let params = Params {
+            delta,
+            ..Params::default()
+        };
+        params.validate()?;
+        Ok(Self::with_params(params.slots_for(capacity), params))
+    }
+}
+
+impl<T, H, O> ElasticHashTable<T, H, O>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    /// Create a table with exactly `size` slots that reports to `observer`,
+    /// `params` must be valid
+    pub(crate) fn with_params_and_observer(size: usize, params: Params, observer: O) -> Self {
+        let max_elements = (size as f64 * (1.0 - params.delta)) as usize;
+
+        let mut hashing = ElasticHashTable {
+            size,
+            data: Vec::with_capacity(size),
+            ctrl: Vec::new(),
+            hashes: H::default(),
+            bucket_offsets: Vec::new(),
+            params,
+            max_elements,
+            batch_max: Vec::new(),
+            current_batch: (0, 0),
+            bucket_load: Vec::new(),
+            tombstone_count: 0,
+            tombstone_bucket_map: Vec::new(),
+            bucket_depth: Vec::new(),
+            generation: 0,
+            #[cfg(feature = "paranoid")]
+            mutations: 0,
+            stats: None,
+            observer,
+        };
+        let sizes = params.bucket_sizes(size);
+        hashing.calc_bucket_size(&sizes);
+        hashing.batch_max = params.batch_sizes(&sizes);
+        hashing.tombstone_bucket_map = vec![0; sizes.len()];
+        hashing.bucket_depth = vec![0; sizes.len()];
+        hashing
+    }
+
+    /// Number of slots a table needs to hold `elements` values at
+    /// δ = 1/2^`delta_factor`
+    pub fn slots_for(elements: usize, delta_factor: i32) -> usize {
+        slots_for(elements, delta(delta_factor))
+    }
+
+    /// Number of values the table holds before it has to grow.
+    ///
+    /// Tombstones count against it until the table is rebuilt.
+    pub fn capacity(&self) -> usize {
+        self.max_elements
+    }
+
+    pub fn get_underlying_size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub(crate) fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Probe sequence of `hash` in bucket `i` (1-based), independent of the
+    /// sequences in the other buckets
+    pub fn sequence(&self, hash: u64, i: i32) -> ElasticProbe {
+        debug_assert!(i > 0);
+        let seq = probe::ProbeSequence::with_prng(
+            elastic_probe::bucket_seed(hash, i as u32),
+            self.get_bucket(i as usize - 1).len(),
+            self.params.strategy,
+            self.params.prng,
+        );
+        ElasticProbe::new(seq)
+    }
+
+    /// Load the group of control bytes a probe at `pos` lands on.
+    ///
+    /// A bucket is split into groups of `Group::WIDTH` slots, the probe
+    /// position selects one of them. Returns the absolute index of the first
+    /// slot, the group and the mask of lanes that belong to the bucket.
+    fn group_at(&self, bucket_idx: usize, pos: usize) -> (usize, Group, BitMask) {
+        let bucket_len = self.get_bucket(bucket_idx).len();
+        let group_start = pos % bucket_len / Group::WIDTH * Group::WIDTH;
+        let base = self.bucket_offsets[bucket_idx] + group_start;
+        let lanes = BitMask::first_lanes(bucket_len - group_start);
+        (base, Group::load(&self.ctrl[base..]), lanes)
+    }
+
+    fn start_walk(&self, hash: u64) -> Walk {
+        Walk {
+            probes: (0..self.bucket_count()).map(|_| None).collect(),
+            hash,
+            k: 0,
+            buckets: self.fresh_walks(),
+            done_bucket: 0,
+            examined: 0,
+            trace: None,
+            next: None,
+        }
+    }
+
+    /// Walk state of a key about which nothing is known yet
+    fn fresh_walks(&self) -> Vec<BucketWalk> {
+        vec![BucketWalk::default(); self.bucket_count()]
+    }
+
+    /// Move `walk` to the next group it has to examine.
+    ///
+    /// Returns the outcome instead once every bucket has been abandoned.
+    fn advance(&self, walk: &mut Walk) -> Option<Search> {
+        loop {
+            walk.k += 1;
+            let Some((i, j)) = elastic_probe::de_phi(walk.k - 1) else {
+                continue;
+            };
+            debug_assert_eq!(
+                elastic_probe::phi(i, j),
+                walk.k - 1,
+                "phi(i,j) != k i: {}, j: {}, k: {}",
+                i,
+                j,
+                walk.k - 1
+            );
+            if i > self.bucket_count() as u32 {
+                continue;
+            }
+            let bucket_idx = i as usize - 1;
+            if walk.buckets[bucket_idx].done {
+                continue;
+            }
+            if j > self.bucket_depth[bucket_idx] {
+                // nothing was ever placed this deep
+                self.trace_step(walk, bucket_idx, j, None, Some(AbandonReason::ProbeDepth));
+                if walk.abandon(bucket_idx) {
+                    return Some(walk.absent());
+                }
+                continue;
+            }
+            let pos = walk.probes[bucket_idx]
+                .get_or_insert_with(|| self.sequence(walk.hash, i as i32))
+                .probe(j);
+            walk.next = Some((bucket_idx, j, pos));
+            return None;
+        }
+    }
+
+    /// Examine the group `walk` was advanced to.
+    ///
+    /// Only values whose tag matches are passed to `eq`. The bucket is
+    /// abandoned when the group has an empty slot or `j` reaches the deepest
+    /// attempt any insert has used in it, as no value can be placed further
+    /// along. The first free slot of the bucket is recorded so that an
+    /// insert does not need to probe again.
+    fn examine(&self, walk: &mut Walk, mut eq: impl FnMut(&T) -> bool) -> Option<Search> {
+        let (bucket_idx, j, pos) = walk.next.take().expect("walk was not advanced");
+        walk.examined += 1;
+        let (base, group, lanes) = self.group_at(bucket_idx, pos);
+
+        for lane in group.match_byte(group::h2(walk.hash)).and(lanes) {
+            if self.hashes.get(base + lane).is_some_and(|h| h != walk.hash) {
+                continue;
+            }
+            if let Slot::Occupied(ref value) = self.data[base + lane] {
+                if eq(value) {
+                    self.trace_step(walk, bucket_idx, j, Some((base, lanes)), None);
+                    return Some(Search::Found(base + lane));
+                }
+            }
+        }
+
+        let bucket = &mut walk.buckets[bucket_idx];
+        bucket.last_j = j;
+        if bucket.free.is_none() {
+            if let Some(lane) = group.match_empty_or_deleted().and(lanes).lowest_set_bit() {
+                bucket.free = Some((base + lane, j));
+            }
+        }
+        let abandoned = if group.match_empty().and(lanes).any_bit_set() {
+            Some(AbandonReason::EmptySlot)
+        } else if j >= self.bucket_depth[bucket_idx] {
+            Some(AbandonReason::ProbeDepth)
+        } else {
+            None
+        };
+        self.trace_step(walk, bucket_idx, j, Some((base, lanes)), abandoned);
+        if abandoned.is_some() && walk.abandon(bucket_idx) {
+            return Some(walk.absent());
+        }
+        None
+    }
+
+    /// Add a lookup step to the trace of `walk`, if it keeps one
+    fn trace_step(
+        &self,
+        walk: &mut Walk,
+        bucket_idx: usize,
+        j: u32,
+        group: Option<(usize, BitMask)>,
+        abandoned: Option<AbandonReason>,
+    ) {
+        if let Some(steps) = &mut walk.trace {
+            steps.push(self.probe_step(bucket_idx, j, Phase::Lookup, group, abandoned));
+        }
+    }
+
+    fn probe_step(
+        &self,
+        bucket_idx: usize,
+        attempt: u32,
+        phase: Phase,
+        group: Option<(usize, BitMask)>,
+        abandoned: Option<AbandonReason>,
+    ) -> ProbeStep {
+        let slots = match group {
+            Some((base, lanes)) => lanes
+                .into_iter()
+                .map(|lane| SlotKind::from(&self.data[base + lane]))
+                .collect(),
+            None => Vec::new(),
+        };
+        ProbeStep {
+            bucket: bucket_idx,
+            attempt,
+            phase,
+            group: group.map(|(base, _)| base),
+            slots,
+            abandoned,
+        }
+    }
+
+    /// Prefetch the memory the next `examine` of `walk` touches
+    fn prefetch(&self, walk: &Walk) {
+        if let Some((bucket_idx, _, pos)) = walk.next {
+            let bucket_len = self.get_bucket(bucket_idx).len();
+            let base =
+                self.bucket_offsets[bucket_idx] + pos % bucket_len / Group::WIDTH * Group::WIDTH;
+            prefetch(&self.ctrl[base]);
+            prefetch(&self.data[base]);
+        }
+    }
+
+    /// Walk the probe sequence of `hash` over all buckets in `phi` order,
+    /// until `eq` accepts a value or every bucket is abandoned.
+    pub(crate) fn search(&self, hash: u64, eq: impl FnMut(&T) -> bool) -> Search {
+        self.observer.on_begin(Operation::Lookup);
+        let mut walk = self.start_walk(hash);
+        let result = self.run_walk(&mut walk, eq);
+        let result = self.finish(&walk, result);
+        self.observer.on_finish(Operation::Lookup);
+        result
+    }
+
+    /// Advance `walk` to its outcome, without recording or reporting it
+    fn run_walk(&self, walk: &mut Walk, mut eq: impl FnMut(&T) -> bool) -> Search {
+        loop {
+            if let Some(result) = self.advance(walk) {
+                return result;
+            }
+            if let Some(result) = self.examine(walk, &mut eq) {
+                return result;
+            }
+        }
+    }
+
+    /// Record and report the probe length of a finished walk
+    fn finish(&self, walk: &Walk, result: Search) -> Search {
+        let found = matches!(result, Search::Found(_));
+        if let Some(stats) = &self.stats {
+            stats.record_lookup(found, walk.examined);
+        }
+        self.observer.on_lookup(found, walk.examined);
+        result
+    }
+
+    /// Like `search` for many hashes, advancing up to `BATCH_LANES` walks in
+    /// lockstep and prefetching the next group of each before examining any
+    /// of them, so that their memory latency overlaps. `eq` receives the
+    /// index of the hash being looked up.
+    pub(crate) fn search_many(
+        &self,
+        hashes: &[u64],
+        mut eq: impl FnMut(usize, &T) -> bool,
+    ) -> Vec<Search> {
+        self.observer.on_begin(Operation::Lookup);
+        let mut results: Vec<Option<Search>> = (0..hashes.len()).map(|_| None).collect();
+        for start in (0..hashes.len()).step_by(BATCH_LANES) {
+            let end = (start + BATCH_LANES).min(hashes.len());
+            let mut pending: Vec<(usize, Walk)> = (start..end)
+                .map(|n| (n, self.start_walk(hashes[n])))
+                .collect();
+            while !pending.is_empty() {
+                pending.retain_mut(|(n, walk)| match self.advance(walk) {
+                    Some(result) => {
+                        results[*n] = Some(self.finish(walk, result));
+                        false
+                    }
+                    None => {
+                        self.prefetch(walk);
+                        true
+                    }
+                });
+                pending.retain_mut(|(n, walk)| match self.examine(walk, |v| eq(*n, v)) {
+                    Some(result) => {
+                        results[*n] = Some(self.finish(walk, result));
+                        false
+                    }
+                    None => true,
+                });
+            }
+        }
+        self.observer.on_finish(Operation::Lookup);
+        results.into_iter().map(Option::unwrap).collect()
+    }
+
+    pub(crate) fn slot(&self, idx: usize) -> &T {
+        match &self.data[idx] {
+            Slot::Occupied(value) => value,
+            _ => unreachable!("slot {} is not occupied", idx),
+        }
+    }
+
+    pub(crate) fn slot_mut(&mut self, idx: usize) -> &mut T {
+        self.paranoid_check(Some(idx));
+        match &mut self.data[idx] {
+            Slot::Occupied(value) => value,
+            _ => unreachable!("slot {} is not occupied", idx),
+        }
+    }
+
+    /// Find the value with hash `hash` accepted by `eq`
+    pub fn find(&self, hash: u64, eq: impl FnMut(&T) -> bool) -> Option<&T> {
+        match self.search(hash, eq) {
+            Search::Found(idx) => Some(self.slot(idx)),
+            Search::Absent(_) => None,
+        }
+    }
+
+    pub fn find_mut(&mut self, hash: u64, eq: impl FnMut(&T) -> bool) -> Option<&mut T> {
+        match self.search(hash, eq) {
+            Search::Found(idx) => Some(self.slot_mut(idx)),
+            Search::Absent(_) => None,
+        }
+    }
+
+    /// Find many values at once, see `search_many`. `eq` receives the index
+    /// of the hash being looked up.
+    pub fn find_many(&self, hashes: &[u64], eq: impl FnMut(usize, &T) -> bool) -> Vec<Option<&T>> {
+        self.search_many(hashes, eq)
+            .into_iter()
+            .map(|search| match search {
+                Search::Found(idx) => Some(self.slot(idx)),
+                Search::Absent(_) => None,
+            })
+            .collect()
+    }
+
+    /// Find mutable references to `N` values at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if two hashes find the same value.
+    pub fn get_disjoint_mut<const N: usize>(
+        &mut self,
+        hashes: [u64; N],
+        eq: impl FnMut(usize, &T) -> bool,
+    ) -> [Option<&mut T>; N] {
+        let mut found: Vec<(usize, usize)> = self
+            .search_many(&hashes, eq)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(n, search)| match search {
+                Search::Found(idx) => Some((idx, n)),
+                Search::Absent(_) => None,
+            })
+            .collect();
+        found.sort_unstable();
+        assert!(
+            found.windows(2).all(|pair| pair[0].0 != pair[1].0),
+            "overlapping keys in get_disjoint_mut"
+        );
+
+        let mut result = [const { None }; N];
+        let mut rest = &mut self.data[..];
+        let mut offset = 0;
+        for (idx, n) in found {
+            let (slot, tail) = std::mem::take(&mut rest)[idx - offset..]
+                .split_first_mut()
+                .unwrap();
+            if let Slot::Occupied(value) = slot {
+                result[n] = Some(value);
+            }
+            rest = tail;
+            offset = idx + 1;
+        }
+        result
+    }
+
+    pub fn find_entry(
+        &mut self,
+        hash: u64,
+        eq: impl FnMut(&T) -> bool,
+    ) -> Result<OccupiedEntry<'_, T, H, O>, AbsentEntry<'_, T, H, O>> {
+        match self.search(hash, eq) {
+            Search::Found(idx) => Ok(OccupiedEntry { table: self, idx }),
+            Search::Absent(_) => Err(AbsentEntry { table: self }),
+        }
+    }
+
+    /// Find the value accepted by `eq`, or the place a new value with hash
+    /// `hash` would go. `hasher` rehashes values if the table has to grow.
+    pub fn entry(
+        &mut self,
+        hash: u64,
+        eq: impl FnMut(&T) -> bool,
+        hasher: impl Fn(&T) -> u64,
+    ) -> Entry<'_, T, H, O> {
+        if self.is_full() {
+            self.grow(&hasher);
+        }
+        match self.search(hash, eq) {
+            Search::Found(idx) => Entry::Occupied(OccupiedEntry { table: self, idx }),
+            Search::Absent(walks) => Entry::Vacant(VacantEntry {
+                table: self,
+                hash,
+                walks,
+            }),
+        }
+    }
+
+    /// Insert a value that is known not to be in the table.
+    ///
+    /// When the table is full it grows to twice its size, rehashing values
+    /// with `hasher` unless `H` caches their hashes.
+    pub fn insert_unique(
+        &mut self,
+        hash: u64,
+        value: T,
+        hasher: impl Fn(&T) -> u64,
+    ) -> OccupiedEntry<'_, T, H, O> {
+        let walks = self.fresh_walks();
+        let idx = self.place(hash, value, &walks, hasher);
+        OccupiedEntry { table: self, idx }
+    }
+
+    /// Remove and return the value with hash `hash` accepted by `eq`
+    pub fn remove(&mut self, hash: u64, eq: impl FnMut(&T) -> bool) -> Option<T> {
+        match self.search(hash, eq) {
+            Search::Found(idx) => Some(self.remove_at(idx)),
+            Search::Absent(_) => None,
+        }
+    }
+
+    fn remove_at(&mut self, idx: usize) -> T {
+        self.observer.on_begin(Operation::Remove);
+        let bucket_idx = self.bucket_of(idx);
+        match std::mem::replace(&mut self.data[idx], Slot::Tombstone) {
+            Slot::Occupied(value) => {
+                self.ctrl[idx] = group::DELETED;
+                self.tombstone_bucket_map[bucket_idx] += 1;
+                self.tombstone_count += 1;
+                self.observer.on_remove(bucket_idx);
+                self.observer.on_finish(Operation::Remove);
+                self.paranoid_check(Some(idx));
+                value
+            }
+            _ => unreachable!("slot {} is not occupied", idx),
+        }
+    }
+
+    /// Load of bucket `bucket_idx` once the batch that first uses it is done
+    fn fill_target(&self, bucket_idx: usize) -> usize {
+        self.params.fill_target(self.get_bucket(bucket_idx).len())
+    }
+
+    /// Load of bucket `bucket_idx` once the batch after that is done too
+    fn full_target(&self, bucket_idx: usize) -> usize {
+        self.params.full_target(self.get_bucket(bucket_idx).len())
+    }
+
+    /// Find a tombstone worth reusing: the first free slot of a bucket
+    /// that turned out to be a tombstone within the first few attempts.
+    fn reusable_tombstone(&self, walks: &[BucketWalk]) -> Option<(usize, usize, u32)> {
+        walks
+            .iter()
+            .enumerate()
+            .find_map(|(bucket_idx, walk)| match walk.free {
+                Some((idx, j))
+                    if j <= self.params.tombstone_scan
+                        && self.tombstone_bucket_map[bucket_idx] > 0
+                        && self.ctrl[idx] == group::DELETED =>
+                {
+                    Some((bucket_idx, idx, j))
+                }
+                _ => None,
+            })
+    }
+
+    /// Layout generation, see `is_stale`
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Whether a search made at layout `generation` no longer applies: the
+    /// table was rebuilt or cleared since, or a free slot recorded by the
+    /// walk has been taken
+    pub(crate) fn is_stale(&self, search: &Search, generation: u64) -> bool {
+        if generation != self.generation {
+            return true;
+        }
+        match search {
+            Search::Found(_) => false,
+            Search::Absent(walks) => walks.iter().any(|b| {
+                b.free
+                    .is_some_and(|(idx, _)| group::is_full(self.ctrl[idx]))
+            }),
+        }
+    }
+
+    /// Whether the batch schedule has no room for another value in an empty slot
+    fn is_full(&self) -> bool {
+        self.bucket_load.iter().sum::<usize>() >= self.max_elements
+    }
+
+    /// Store a value that is known to be absent and return its slot.
+    ///
+    /// `walks` is what the lookup walk learned about the free slots of each
+    /// bucket, probing resumes from there when it is not enough. A full
+    /// table grows first, rehashing with `hasher`.
+    pub(crate) fn place(
+        &mut self,
+        hash: u64,
+        value: T,
+        walks: &[BucketWalk],
+        hasher: impl Fn(&T) -> u64,
+    ) -> usize {
+        if self.reusable_tombstone(walks).is_none() && self.is_full() {
+            self.grow(&hasher);
+            let walks = self.fresh_walks();
+            return self.place_without_growth(hash, value, &walks);
+        }
+        self.place_without_growth(hash, value, walks)
+    }
+
+    fn place_without_growth(&mut self, hash: u64, value: T, walks: &[BucketWalk]) -> usize {
+        self.observer.on_begin(Operation::Insert);
+        // the lookup examined these groups already
+        let mut probes = walks.iter().map(|walk| walk.last_j).sum();
+        let idx = self.place_probed(hash, value, walks, &mut probes);
+        let bucket_idx = self.bucket_of(idx);
+        if let Some(stats) = &self.stats {
+            stats.record_insert(bucket_idx, probes);
+        }
+        self.observer.on_insert(bucket_idx, probes);
+        self.observer.on_finish(Operation::Insert);
+        idx
+    }
+
+    /// `place_without_growth`, adding the groups it examines to `probes`
+    fn place_probed(
+        &mut self,
+        hash: u64,
+        value: T,
+        walks: &[BucketWalk],
+        probes: &mut u32,
+    ) -> usize {
+        if let Some((bucket_idx, idx, j)) = self.reusable_tombstone(walks) {
+            self.occupy(bucket_idx, idx, j, hash, value);
+            return idx;
+        }
+
+        match self.insert_branch() {
+            InsertBranch::Grow => panic!("ElasticHashTable is full"),
+            InsertBranch::Limited { bucket, limit } => {
+                let value = match self.try_seq(hash, value, bucket + 1, limit as i32, walks, probes)
+                {
+                    ControlFlow::Break(idx) => return idx,
+                    ControlFlow::Continue(value) => value,
+                };
+                if let ControlFlow::Break(idx) =
+                    self.try_seq(hash, value, bucket + 2, i32::MAX, walks, probes)
+                {
+                    return idx;
+                }
+            }
+            InsertBranch::Next { bucket } | InsertBranch::Only { bucket } => {
+                if let ControlFlow::Break(idx) =
+                    self.try_seq(hash, value, bucket + 1, i32::MAX, walks, probes)
+                {
+                    return idx;
+                }
+            }
+        }
+        unreachable!()
+    }
+
+    /// Decide where the next insert into an empty slot goes, following the
+    /// batch schedule
+    fn insert_branch(&self) -> InsertBranch {
+        if self.is_full() {
+            return InsertBranch::Grow;
+        }
+        InsertBranch::choose(
+            &self.params,
+            self.current_batch.1,
+            &self.bucket_load,
+            |bucket_idx| self.get_bucket(bucket_idx).len(),
+        )
+    }
+
+    /// Trace the probe path of `hash`: every step of the lookup in `phi`
+    /// order and, when `eq` accepts no value, where an insert would put it.
+    ///
+    /// Does not record probe statistics.
+    pub fn explain(&self, hash: u64, eq: impl FnMut(&T) -> bool) -> ProbeTrace {
+        let mut walk = self.start_walk(hash);
+        walk.trace = Some(Vec::new());
+        let search = self.run_walk(&mut walk, eq);
+        let mut steps = walk.trace.take().unwrap_or_default();
+        let outcome = match search {
+            Search::Found(index) => TraceOutcome::Found {
+                index,
+                bucket: self.bucket_of(index),
+                attempt: steps.last().map_or(0, |step| step.attempt),
+            },
+            Search::Absent(walks) => self.plan_insert(hash, &walks, &mut steps),
+        };
+        ProbeTrace {
+            hash,
+            steps,
+            outcome,
+        }
+    }
+
+    /// Where `place_without_growth` would put a value, adding the groups it
+    /// would examine beyond the lookup to `steps`
+    fn plan_insert(
+        &self,
+        hash: u64,
+        walks: &[BucketWalk],
+        steps: &mut Vec<ProbeStep>,
+    ) -> TraceOutcome {
+        if let Some((bucket, index, attempt)) = self.reusable_tombstone(walks) {
+            return TraceOutcome::Insert {
+                index,
+                bucket,
+                attempt,
+            };
+        }
+        let (bucket, limit) = match self.insert_branch() {
+            InsertBranch::Grow => return TraceOutcome::Grow,
+            InsertBranch::Limited { bucket, limit } => (bucket, Some(limit)),
+            InsertBranch::Next { bucket } | InsertBranch::Only { bucket } => (bucket, None),
+        };
+        let max_try = limit.map_or(i32::MAX, |limit| limit as i32);
+        if let Some(outcome) = self.traced_free_slot(hash, bucket, max_try, walks, steps) {
+            return outcome;
+        }
+        // only the f(ε) limit makes a bucket run out of attempts
+        steps.push(self.probe_step(
+            bucket,
+            max_try as u32,
+            Phase::Insert,
+            None,
+            Some(AbandonReason::ProbeLimit),
+        ));
+        self.traced_free_slot(hash, bucket + 1, i32::MAX, walks, steps)
+            .expect("an unlimited probe always finds a free slot")
+    }
+
+    /// `free_slot`, adding the groups it examines to `steps`
+    fn traced_free_slot(
+        &self,
+        hash: u64,
+        bucket_idx: usize,
+        max_try: i32,
+        walks: &[BucketWalk],
+        steps: &mut Vec<ProbeStep>,
+    ) -> Option<TraceOutcome> {
+        let found = self.free_slot(hash, bucket_idx, max_try, walks, |j, base, lanes| {
+            steps.push(self.probe_step(bucket_idx, j, Phase::Insert, Some((base, lanes)), None))
+        });
+        found.map(|(index, attempt)| TraceOutcome::Insert {
+            index,
+            bucket: bucket_idx,
+            attempt,
+        })
+    }
+
+    /// Snapshot of the slots of every bucket, for rendering
+    pub fn layout(&self) -> Layout {
+        Layout {
+            buckets: (0..self.bucket_count())
+                .map(|i| {
+                    let slots = self.get_bucket(i);
+                    BucketLayout {
+                        size: slots.len(),
+                        load: self.bucket_load[i],
+                        tombstones: self.tombstone_bucket_map[i],
+                        slots: slots.iter().map(SlotKind::from).collect(),
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// Snapshot of the batch schedule: the running batch, the load of every
+    /// bucket and where the next insert goes
+    pub fn batch_status(&self) -> BatchStatus {
+        BatchStatus {
+            batch: self.current_batch.1,
+            batch_count: self.current_batch.0,
+            batch_max: self.batch_max.clone(),
+            buckets: (0..self.bucket_count())
+                .map(|i| BucketStatus {
+                    load: self.bucket_load[i],
+                    capacity: self.get_bucket(i).len(),
+                    tombstones: self.tombstone_bucket_map[i],
+                    epsilon: self.epsilon(i),
+                })
+                .collect(),
+            next_insert: self.insert_branch(),
+        }
+    }
+
+    /// Place the value into bucket `i` within `max_try` attempts
+    fn try_seq(
+        &mut self,
+        hash: u64,
+        value: T,
+        i: usize,
+        max_try: i32,
+        walks: &[BucketWalk],
+        probes: &mut u32,
+    ) -> ControlFlow<usize, T> {
+        match self.free_slot(hash, i - 1, max_try, walks, |_, _, _| *probes += 1) {
+            Some((idx, j)) => {
+                self.occupy(i - 1, idx, j, hash, value);
+                ControlFlow::Break(idx)
+            }
+            None => ControlFlow::Continue(value),
+        }
+    }
+
+    /// First free slot of bucket `bucket_idx` within `max_try` attempts,
+    /// and the attempt it is found at.
+    ///
+    /// The walk done by `search` already covers the first attempts of the
+    /// bucket, so probing only resumes where that walk stopped. `visit` sees
+    /// every further group examined, with its attempt, first slot and lanes.
+    fn free_slot(
+        &self,
+        hash: u64,
+        bucket_idx: usize,
+        max_try: i32,
+        walks: &[BucketWalk],
+        mut visit: impl FnMut(u32, usize, BitMask),
+    ) -> Option<(usize, u32)> {
+        let walk = walks[bucket_idx];
+        if let Some((idx, j)) = walk.free {
+            return (j as i64 <= max_try as i64).then_some((idx, j));
+        }
+
+        // the same positions as the walk in `search`, whatever the strategy
+        let mut probe = self.sequence(hash, bucket_idx as i32 + 1);
+        for j in walk.last_j as i32 + 1..=max_try {
+            let pos = probe.probe(j as u32);
+            let (base, group, lanes) = self.group_at(bucket_idx, pos);
+            visit(j as u32, base, lanes);
+            if let Some(lane) = group.match_empty_or_deleted().and(lanes).lowest_set_bit() {
+                return Some((base + lane, j as u32));
+            }
+        }
+        None
+    }
+
+    /// Store the value in the free slot `idx` of bucket `bucket_idx`, found
+    /// at attempt `j`.
+    ///
+    /// Reusing a tombstone does not change the bucket load, filling an
+    /// empty slot counts towards the current batch.
+    fn occupy(&mut self, bucket_idx: usize, idx: usize, j: u32, hash: u64, value: T) {
+        let depth = &mut self.bucket_depth[bucket_idx];
+        *depth = (*depth).max(j);
+        self.ctrl[idx] = group::h2(hash);
+        self.hashes.set(idx, hash);
+        let slot = std::mem::replace(&mut self.data[idx], Slot::Occupied(value));
+        if let Slot::Tombstone = slot {
+            self.tombstone_bucket_map[bucket_idx] -= 1;
+            self.tombstone_count -= 1;
+            self.observer.on_tombstone_reuse(bucket_idx);
+            self.paranoid_check(Some(idx));
+            return;
+        }
+        debug_assert!(matches!(slot, Slot::Empty));
+        self.bucket_load[bucket_idx] += 1;
+        self.current_batch.0 += 1;
+        if self.current_batch.0 >= self.batch_max[self.current_batch.1] {
+            self.current_batch = (0, self.current_batch.1 + 1);
+            self.observer.on_batch_advance(self.current_batch.1);
+            let i = self.current_batch.1 - 1;
+            #[cfg(any(debug_assertions, test))]
+            {
+                for j in 0..i {
+                    let bucket_load = self.bucket_load[j];
+                    let expected = self.full_target(j);
+                    assert_eq!(
+                        expected,
+                        bucket_load,
+                        "when done batch {}, A_{} has {} elements, while it should have {}",
+                        i,
+                        j + 1,
+                        bucket_load,
+                        expected
+                    );
+                }
+            }
+            #[cfg(any(debug_assertions, test))]
+            if i < self.bucket_count() {
+                let bucket_load = self.bucket_load[i];
+                let expected = self.fill_target(i);
+                assert_eq!(
+                    expected,
+                    bucket_load,
+                    "when done batch {}, A_{} has {} elements, while it should have {}",
+                    i,
+                    i + 1,
+                    bucket_load,
+                    expected
+                );
+            }
+        }
+        self.paranoid_check(Some(idx));
+    }
+
+    fn epsilon(&self, i: usize) -> f64 {
+        let bucket_size = self.get_bucket(i).len();
+        let bucket_load = self.bucket_load[i];
+        let load_factor = bucket_load as f64 / bucket_size as f64;
+        1.0 - load_factor
+    }
+
+    /// Lay the buckets of `sizes` out one after the other, see
+    /// `Params::bucket_sizes`
+    fn calc_bucket_size(&mut self, sizes: &[usize]) {
+        let size = sizes.iter().sum();
+        self.bucket_offsets = Vec::with_capacity(sizes.len());
+        let mut offset = 0;
+        for &bucket_size in sizes {
+            self.bucket_offsets.push(offset);
+            offset += bucket_size;
+        }
+        self.bucket_load = vec![0; sizes.len()];
+
+        self.data = Vec::with_capacity(size);
+        self.data.resize_with(size, || Slot::Empty);
+        self.ctrl = vec![group::EMPTY; self.data.len() + Group::WIDTH];
+        self.hashes.reset(self.data.len());
+    }
+
+    /// All slots, bucket after bucket
+    pub fn data(&self) -> &[Slot<T>] {
+        &self.data
+    }
+
+    pub fn get_bucket(&self, bucket_idx: usize) -> &[Slot<T>] {
+        if bucket_idx >= self.bucket_offsets.len() {
+            return &[];
+        }
+
+        let start = self.bucket_offsets[bucket_idx];
+        let end = if bucket_idx + 1 < self.bucket_offsets.len() {
+            self.bucket_offsets[bucket_idx + 1]
+        } else {
+            self.data.len()
+        };
+
+        &self.data[start..end]
+    }
+
+    pub fn bucket_count(&self) -> usize {
+        self.bucket_offsets.len()
+    }
+
+    /// Bucket that holds the slot `idx`
+    fn bucket_of(&self, idx: usize) -> usize {
+        self.bucket_offsets.partition_point(|&start| start <= idx) - 1
+    }
+
+    /// Bytes the table allocates: slots, control bytes, cached hashes and
+    /// the per-bucket bookkeeping. Memory owned by the values themselves is
+    /// not counted.
+    pub fn allocation_size(&self) -> usize {
+        use std::mem::size_of;
+        let per_bucket = self.bucket_offsets.capacity()
+            + self.bucket_load.capacity()
+            + self.batch_max.capacity()
+            + self.tombstone_bucket_map.capacity();
+        self.data.capacity() * size_of::<Slot<T>>()
+            + self.ctrl.capacity()
+            + self.hashes.allocation_size()
+            + per_bucket * size_of::<usize>()
+            + self.bucket_depth.capacity() * size_of::<u32>()
+    }
+
+    /// Start recording probe-length histograms of inserts and lookups
+    pub fn enable_probe_stats(&mut self) {
+        if self.stats.is_none() {
+            self.stats = Some(Box::new(ProbeStats::new(self.bucket_count())));
+        }
+    }
+
+    /// The probe-length histograms, if `enable_probe_stats` was called
+    pub fn probe_stats(&self) -> Option<&ProbeStats> {
+        self.stats.as_deref()
+    }
+
+    /// The observer the table reports its operations to
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    /// Largest probe attempt `j` an insert has used in bucket `bucket_idx`.
+    ///
+    /// Lookups never probe a bucket deeper than this.
+    pub fn probe_depth(&self, bucket_idx: usize) -> u32 {
+        self.bucket_depth.get(bucket_idx).copied().unwrap_or(0)
+    }
+
+    /// Check the internal invariants of the table.
+    ///
+    /// Compares the recorded loads and tombstone counts with the slots,
+    /// checks the control bytes and the bucket fill levels the batch
+    /// schedule allows, then looks up every value with `hasher` and `eq` to
+    /// make sure it is found in its own slot. These lookups are not recorded
+    /// in the probe statistics or reported to the observer.
+    pub fn validate(
+        &self,
+        hasher: impl Fn(&T) -> u64,
+        eq: impl Fn(&T, &T) -> bool,
+    ) -> Result<(), InvariantViolation> {
+        self.validate_structure()?;
+        (0..self.data.len()).try_for_each(|index| self.validate_value(index, &hasher, &eq))
+    }
+
+    /// The checks of `validate` for the value in slot `index`, if any: its
+    /// cached hash and control byte match `hasher`, and looking it up finds
+    /// this very slot
+    pub(crate) fn validate_value(
+        &self,
+        index: usize,
+        hasher: impl Fn(&T) -> u64,
+        eq: impl Fn(&T, &T) -> bool,
+    ) -> Result<(), InvariantViolation> {
+        let Slot::Occupied(value) = &self.data[index] else {
+            return Ok(());
+        };
+        let hash = hasher(value);
+        if self.hashes.get(index).is_some_and(|cached| cached != hash) {
+            return Err(InvariantViolation::CachedHash { index });
+        }
+        // not a lookup of the caller, so it is neither recorded nor observed
+        let mut walk = self.start_walk(hash);
+        match self.run_walk(&mut walk, |other| eq(value, other)) {
+            Search::Found(other) if other == index => {}
+            Search::Found(other) => return Err(InvariantViolation::Duplicate { index, other }),
+            Search::Absent(_) => return Err(InvariantViolation::Unreachable { index }),
+        }
+        if self.ctrl[index] != group::h2(hash) {
+            return Err(InvariantViolation::ControlByte { index });
+        }
+        Ok(())
+    }
+
+    /// The checks of `validate` that need neither hashes nor equality
+    fn validate_structure(&self) -> Result<(), InvariantViolation> {
+        for bucket in 0..self.bucket_count() {
+            let start = self.bucket_offsets[bucket];
+            let (mut load, mut dead) = (0, 0);
+            for (offset, slot) in self.get_bucket(bucket).iter().enumerate() {
+                self.validate_slot(start + offset)?;
+                dead += matches!(slot, Slot::Tombstone) as usize;
+                load += !matches!(slot, Slot::Empty) as usize;
+            }
+            if load != self.bucket_load[bucket] {
+                return Err(InvariantViolation::BucketLoad {
+                    bucket,
+                    recorded: self.bucket_load[bucket],
+                    actual: load,
+                });
+            }
+            if dead != self.tombstone_bucket_map[bucket] {
+                return Err(InvariantViolation::BucketTombstones {
+                    bucket,
+                    recorded: self.tombstone_bucket_map[bucket],
+                    actual: dead,
+                });
+            }
+        }
+        if let Some(offset) = self.ctrl[self.data.len()..]
+            .iter()
+            .position(|&ctrl| ctrl != group::EMPTY)
+        {
+            return Err(InvariantViolation::ControlByte {
+                index: self.data.len() + offset,
+            });
+        }
+        self.validate_counts()
+    }
+
+    /// The control byte of slot `index` matches the slot
+    fn validate_slot(&self, index: usize) -> Result<(), InvariantViolation> {
+        let ctrl = self.ctrl[index];
+        let matches = match &self.data[index] {
+            Slot::Empty => ctrl == group::EMPTY,
+            Slot::Tombstone => ctrl == group::DELETED,
+            Slot::Occupied(_) => group::is_full(ctrl),
+        };
+        if !matches {
+            return Err(InvariantViolation::ControlByte { index });
+        }
+        Ok(())
+    }
+
+    /// The checks of `validate_structure` on the recorded counts alone,
+    /// which take time in the number of buckets rather than slots
+    fn validate_counts(&self) -> Result<(), InvariantViolation> {
+        let tombstones = self.tombstone_bucket_map.iter().sum::<usize>();
+        if tombstones != self.tombstone_count {
+            return Err(InvariantViolation::TombstoneCount {
+                recorded: self.tombstone_count,
+                actual: tombstones,
+            });
+        }
+
+        let (done, batch) = self.current_batch;
+        let scheduled = self.batch_max[..batch].iter().sum::<usize>() + done;
+        let load = self.bucket_load.iter().sum::<usize>();
+        if load != scheduled {
+            return Err(InvariantViolation::BatchCount {
+                batch,
+                recorded: scheduled,
+                actual: load,
+            });
+        }
+        for bucket in 0..self.bucket_count() {
+            let (min, max) = self.batch_range(batch, bucket);
+            let load = self.bucket_load[bucket];
+            if load < min || load > max {
+                return Err(InvariantViolation::BatchFill {
+                    batch,
+                    bucket,
+                    load,
+                    min,
+                    max,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Range of loads bucket `bucket_idx` may have while `batch` is running
+    fn batch_range(&self, batch: usize, bucket_idx: usize) -> (usize, usize) {
+        let last = bucket_idx + 1 == self.bucket_count();
+        if batch > bucket_idx + 1 && !last {
+            // both batches that use the bucket are done
+            let full = self.full_target(bucket_idx);
+            (full, full)
+        } else if batch > bucket_idx {
+            // the last bucket keeps filling up in the last batch
+            let max = if last {
+                self.get_bucket(bucket_idx).len()
+            } else {
+                self.full_target(bucket_idx)
+            };
+            (self.fill_target(bucket_idx), max)
+        } else if batch == bucket_idx {
+            (0, self.fill_target(bucket_idx))
+        } else {
+            (0, 0)
+        }
+    }
+
+    /// With the `paranoid` feature, panic unless the bookkeeping of the
+    /// table is intact after a mutation of slot `idx`, or of all slots.
+    ///
+    /// The slot and the recorded counts are checked every time, all slots
+    /// only when `full_check_due`.
+    #[allow(unused_variables)]
+    fn paranoid_check(&mut self, idx: Option<usize>) {
+        #[cfg(feature = "paranoid")]
+        {
+            self.mutations += 1;
+            let result = match idx {
+                Some(idx) if !self.full_check_due() => self
+                    .validate_slot(idx)
+                    .and_then(|()| self.validate_counts()),
+                _ => self.validate_structure(),
+            };
+            if let Err(violation) = result {
+                panic!("elastic hash table is corrupted: {}", violation);
+            }
+        }
+    }
+
+    /// Whether the paranoid checks validate every slot after the last
+    /// mutation: once in `max(PARANOID_INTERVAL, slots)` mutations, so that
+    /// they take constant time per mutation on average
+    #[cfg(feature = "paranoid")]
+    pub(crate) fn full_check_due(&self) -> bool {
+        self.mutations
+            .is_multiple_of((self.size as u64).max(PARANOID_INTERVAL))
+    }
+
+    pub fn len(&self) -> usize {
+        self.bucket_load.iter().sum::<usize>() - self.tombstone_count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&mut self) {
+        for item in self.data.iter_mut() {
+            *item = Slot::Empty;
+        }
+        self.ctrl.fill(group::EMPTY);
+        for load in self.bucket_load.iter_mut() {
+            *load = 0;
+        }
+        self.current_batch = (0, 0);
+        self.tombstone_count = 0;
+        for i in self.tombstone_bucket_map.iter_mut() {
+            *i = 0;
+        }
+        for depth in self.bucket_depth.iter_mut() {
+            *depth = 0;
+        }
+        self.generation += 1;
+        self.paranoid_check(None);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.data.iter().filter_map(|slot| match slot {
+            Slot::Occupied(value) => Some(value),
+            _ => None,
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.data.iter_mut().filter_map(|slot| match slot {
+            Slot::Occupied(value) => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Rebuild the table with `size` slots, re-inserting every value.
+    ///
+    /// Hashes come from the cache when `H` keeps them, from `hasher`
+    /// otherwise.
+    fn rebuild(&mut self, size: usize, hasher: impl Fn(&T) -> u64) {
+        // moving the values is not an insert worth recording or observing,
+        // so they go into a table without stats or observer first
+        let mut table = ElasticHashTable::<T, H, NoObserver>::with_params_and_observer(
+            size,
+            self.params,
+            NoObserver,
+        );
+        let walks = table.fresh_walks();
+        let data = std::mem::take(&mut self.data);
+        for (idx, slot) in data.into_iter().enumerate() {
+            if let Slot::Occupied(value) = slot {
+                let hash = match self.hashes.get(idx) {
+                    Some(hash) => hash,
+                    None => hasher(&value),
+                };
+                table.place_without_growth(hash, value, &walks);
+            }
+        }
+        let ElasticHashTable {
+            size,
+            data,
+            ctrl,
+            hashes,
+            bucket_offsets,
+            bucket_load,
+            params,
+            max_elements,
+            batch_max,
+            current_batch,
+            tombstone_count,
+            tombstone_bucket_map,
+            bucket_depth,
+            generation: _,
+            #[cfg(feature = "paranoid")]
+                mutations: _,
+            stats: _,
+            observer: NoObserver,
+        } = table;
+        self.size = size;
+        self.data = data;
+        self.ctrl = ctrl;
+        self.hashes = hashes;
+        self.bucket_offsets = bucket_offsets;
+        self.bucket_load = bucket_load;
+        self.params = params;
+        self.max_elements = max_elements;
+        self.batch_max = batch_max;
+        self.current_batch = current_batch;
+        self.tombstone_count = tombstone_count;
+        self.tombstone_bucket_map = tombstone_bucket_map;
+        self.bucket_depth = bucket_depth;
+        self.generation += 1;
+        let bucket_count = self.bucket_count();
+        if let Some(stats) = &mut self.stats {
+            stats.track_buckets(bucket_count);
+        }
+        self.paranoid_check(None);
+    }
+
+    /// Rebuild the table with `size` slots and report the new size
+    fn resize(&mut self, size: usize, hasher: impl Fn(&T) -> u64) {
+        self.observer.on_begin(Operation::Resize);
+        let old_size = self.size;
+        self.rebuild(size, hasher);
+        self.observer.on_resize(old_size, self.size);
+        self.observer.on_finish(Operation::Resize);
+    }
+
+    /// Make room once the batch schedule is used up: double the table, or
+    /// rebuild it in place when most of the load is tombstones, so that
+    /// inserts and removes in turn do not grow it without bound
+    fn grow(&mut self, hasher: impl Fn(&T) -> u64) {
+        if self.len() < self.max_elements / 2 {
+            self.compact(hasher);
+        } else {
+            self.resize(self.size * 2, hasher);
+        }
+    }
+
+    /// Make room for at least `additional` more values without growing.
+    ///
+    /// Rebuilds the bucket geometry when the remaining capacity is not
+    /// enough, which also drops all tombstones.
+    pub fn reserve(&mut self, additional: usize, hasher: impl Fn(&T) -> u64) {
+        let used = self.bucket_load.iter().sum::<usize>();
+        if used + additional > self.max_elements {
+            let size = self.params.slots_for(self.len() + additional);
+            self.resize(size.max(self.size), hasher);
+        }
+    }
+
+    /// Shrink the table as far as possible while it still holds
+    /// `min_capacity` values, and never below its length
+    pub fn shrink_to(&mut self, min_capacity: usize, hasher: impl Fn(&T) -> u64) {
+        let size = self.params.slots_for(self.len().max(min_capacity));
+        if size < self.size {
+            self.resize(size, hasher);
+        }
+    }
+
+    pub fn shrink_to_fit(&mut self, hasher: impl Fn(&T) -> u64) {
+        self.shrink_to(0, hasher);
+    }
+
+    /// Drop all tombstones by rebuilding the table in place.
+    ///
+    /// This also restarts the batch schedule, so the space taken by removed
+    /// values becomes available to the elastic insertion again.
+    pub fn compact(&mut self, hasher: impl Fn(&T) -> u64) {
+        self.observer.on_begin(Operation::Compact);
+        let tombstones = self.tombstone_count;
+        self.rebuild(self.size, hasher);
+        self.observer.on_compact(tombstones);
+        self.observer.on_finish(Operation::Compact);
+    }
+}
+
+impl<T, H, O> Default for ElasticHashTable<T, H, O>
+where
+    H: HashCache,
+    O: ElasticObserver + Default,
+{
+    fn default() -> Self {
+        ElasticHashTable::with_capacity(16)
+    }
+}
+
+impl<T, H, O> IntoIterator for ElasticHashTable<T, H, O>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        IntoIter {
+            data: self.data.into_iter(),
+        }
+    }
+}
+
+/// Owning iterator over the values of an `ElasticHashTable`
+pub struct IntoIter<T> {
+    data: std::vec::IntoIter<Slot<T>>,
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.data.find_map(|slot| match slot {
+            Slot::Occupied(value) => Some(value),
+            _ => None,
+        })
+    }
+}
+
+/// A view into a single slot of the table, see `ElasticHashTable::entry`
+pub enum Entry<'a, T, H = NoHashCache, O = NoObserver>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    Occupied(OccupiedEntry<'a, T, H, O>),
+    Vacant(VacantEntry<'a, T, H, O>),
+}
+
+/// A slot holding a value
+pub struct OccupiedEntry<'a, T, H = NoHashCache, O = NoObserver>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    table: &'a mut ElasticHashTable<T, H, O>,
+    idx: usize,
+}
+
+impl<'a, T, H, O> OccupiedEntry<'a, T, H, O>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub fn get(&self) -> &T {
+        self.table.slot(self.idx)
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.table.slot_mut(self.idx)
+    }
+
+    pub fn into_mut(self) -> &'a mut T {
+        self.table.slot_mut(self.idx)
+    }
+
+    /// Remove the value, leaving a tombstone
+    pub fn remove(self) -> T {
+        self.table.remove_at(self.idx)
+    }
+
+    #[cfg(feature = "paranoid")]
+    pub(crate) fn index(&self) -> usize {
+        self.idx
+    }
+
+    /// `remove`, handing back the table to check it afterwards
+    #[cfg(feature = "paranoid")]
+    pub(crate) fn remove_from_table(self) -> (T, &'a mut ElasticHashTable<T, H, O>) {
+        (self.table.remove_at(self.idx), self.table)
+    }
+
+    #[cfg(feature = "paranoid")]
+    pub(crate) fn table(&self) -> &ElasticHashTable<T, H, O> {
+        self.table
+    }
+
+    pub fn into_table(self) -> &'a mut ElasticHashTable<T, H, O> {
+        self.table
+    }
+}
+
+/// The place a value would go, remembering what the lookup found out so
+/// that inserting does not probe again
+pub struct VacantEntry<'a, T, H = NoHashCache, O = NoObserver>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    table: &'a mut ElasticHashTable<T, H, O>,
+    hash: u64,
+    walks: Vec<BucketWalk>,
+}
+
+impl<'a, T, H, O> VacantEntry<'a, T, H, O>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub fn insert(self, value: T) -> OccupiedEntry<'a, T, H, O> {
+        let idx = self
+            .table
+            .place_without_growth(self.hash, value, &self.walks);
+        OccupiedEntry {
+            table: self.table,
+            idx,
+        }
+    }
+
+    pub fn into_table(self) -> &'a mut ElasticHashTable<T, H, O> {
+        self.table
+    }
+}
+
+/// Returned by `find_entry` when no value matched
+pub struct AbsentEntry<'a, T, H = NoHashCache, O = NoObserver>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    table: &'a mut ElasticHashTable<T, H, O>,
+}
+
+impl<'a, T, H, O> AbsentEntry<'a, T, H, O>
+where
+    H: HashCache,
+    O: ElasticObserver,
+{
+    pub fn into_table(self) -> &'a mut ElasticHashTable<T, H, O> {
+        self.table
+    }
+}
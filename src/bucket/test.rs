@@ -2,6 +2,19 @@ use crate::probe;
 
 use super::*;
 
+/// Random number generator of the tests, seeded from `ELASTIC_TEST_SEED`
+/// when it is set and from the OS otherwise. The seed is printed, so a
+/// failure can be reproduced by running the test again with it.
+pub(crate) fn test_rng() -> rand::rngs::StdRng {
+    use rand::{Rng, SeedableRng};
+    let seed = match std::env::var("ELASTIC_TEST_SEED") {
+        Ok(seed) => seed.parse().expect("ELASTIC_TEST_SEED is not a u64"),
+        Err(_) => rand::rng().random(),
+    };
+    eprintln!("ELASTIC_TEST_SEED={}", seed);
+    rand::rngs::StdRng::seed_from_u64(seed)
+}
+
 #[test]
 pub(crate) fn test_bucket_size() {
     let hash = ElasticHashing::new(10, 1);
@@ -23,28 +36,38 @@ pub(crate) fn test_insert() {
     let mut hash = ElasticHashing::new(4096, 3);
     let empty = 4096 / 8;
     let space = 4096 - empty; // 112
-    let mut rng = rand::rng();
-    let data = (0..space)
-        .map(|_| rng.random_range(0..1000000))
+    let mut rng = test_rng();
+    let data = Keys::uniform(rng.random(), 1000000)
+        .map(|k| k as i32)
+        .take(space)
         .collect::<Vec<_>>();
-    for i in 0..space {
+    for x in data.iter() {
         // well, we don't do assert here, because insert it self has assertions
-        hash.insert(data[i], data[i]);
+        hash.insert(*x, *x);
     }
-    for i in 0..space {
-        assert_eq!(hash.get(&data[i]), Some(&data[i]));
+    for x in data.iter() {
+        assert_eq!(hash.get(x), Some(x));
     }
-    let data = (0..space)
-        .map(|_| rng.random_range(-1000000..0))
+    let data = Keys::uniform(rng.random(), 1000000)
+        .map(|k| -(k as i32) - 1)
+        .take(space)
         .collect::<Vec<_>>();
     probe::reset_probe_num();
-    for i in 0..space {
-        assert_eq!(hash.get(&data[i]), None);
+    hash.enable_probe_stats();
+    for x in data.iter() {
+        assert_eq!(hash.get(x), None);
     }
     eprintln!(
         "probe num: {}",
         probe::get_probe_num() as f64 / space as f64
     );
+    let misses = hash.probe_stats().unwrap().misses();
+    eprintln!(
+        "miss probes p50: {} p99: {} max: {}",
+        misses.percentile(0.5),
+        misses.percentile(0.99),
+        misses.max()
+    );
 }
 
 #[test]
@@ -152,12 +175,12 @@ fn test_elastic_hashmap_index() {
     map.insert("key2".to_string(), "value2".to_string());
 
     // test index operator
-    assert_eq!(&map["key1".to_string()], "value1");
-    assert_eq!(&map["key2".to_string()], "value2");
+    assert_eq!(&map["key1"], "value1");
+    assert_eq!(&map["key2"], "value2");
 
     // test mutable index operator
-    map["key1".to_string()] = "new_value".to_string();
-    assert_eq!(&map["key1".to_string()], "new_value");
+    map["key1"] = "new_value".to_string();
+    assert_eq!(&map["key1"], "new_value");
 }
 
 #[test]
@@ -253,7 +276,7 @@ fn test_elastic_hashmap_complex_keys() {
 fn test_elastic_hashmap_tombstone() {
     use rand::Rng;
     let mut map = ElasticHashMap::<i32, i32>::with_capacity(32);
-    let mut rng = rand::rng();
+    let mut rng = test_rng();
 
     // first stage: insert some initial data
     let initial_data: Vec<(i32, i32)> = (0..20).map(|i| (i, rng.random_range(0..1000))).collect();
@@ -302,3 +325,1699 @@ fn test_elastic_hashmap_tombstone() {
         avg_probe_first, avg_probe_query
     );
 }
+
+#[test]
+fn test_elastic_hashmap_reinsert_after_remove() {
+    let mut map = ElasticHashMap::<i32, i32>::with_capacity(64);
+
+    for i in 0..40 {
+        assert_eq!(map.insert(i, i), None);
+    }
+    for i in 0..20 {
+        assert_eq!(map.remove(&i), Some(i));
+    }
+    assert_eq!(map.len(), 20);
+
+    // reused tombstones must not be counted twice
+    for i in 0..20 {
+        assert_eq!(map.insert(i, -i), None);
+    }
+    assert_eq!(map.len(), 40);
+    for i in 0..40 {
+        let expected = if i < 20 { -i } else { i };
+        assert_eq!(map.get(&i), Some(&expected));
+    }
+
+    // updating an existing key returns the old value and keeps the length
+    assert_eq!(map.insert(5, 50), Some(-5));
+    assert_eq!(map.len(), 40);
+}
+
+#[test]
+fn test_elastic_hashmap_probe_depth() {
+    let mut map = ElasticHashMap::<i32, i32>::with_capacity(1024);
+    for i in 0..map.bucket_count() {
+        assert_eq!(map.probe_depth(i), 0);
+    }
+    assert_eq!(map.get(&1), None);
+
+    for i in 0..800 {
+        map.insert(i, i);
+    }
+    assert!(map.probe_depth(0) >= 1);
+    for i in 0..800 {
+        assert_eq!(map.get(&i), Some(&i));
+    }
+    for i in 800..1600 {
+        assert_eq!(map.get(&i), None);
+    }
+
+    map.clear();
+    for i in 0..map.bucket_count() {
+        assert_eq!(map.probe_depth(i), 0);
+    }
+}
+
+#[test]
+fn test_group_match() {
+    use super::group::{self, GenericGroup, Group};
+
+    let mut ctrl = vec![group::EMPTY; 2 * Group::WIDTH];
+    ctrl[0] = 0x12;
+    ctrl[2] = group::DELETED;
+    ctrl[3] = 0x7f;
+    ctrl[5] = 0x12;
+    ctrl[7] = 0x00;
+
+    for (expected, matched) in [
+        (
+            vec![0, 5],
+            Group::load(&ctrl).match_byte(0x12).into_iter().collect(),
+        ),
+        (
+            vec![0, 5],
+            GenericGroup::load(&ctrl)
+                .match_byte(0x12)
+                .into_iter()
+                .collect::<Vec<_>>(),
+        ),
+        (
+            vec![7],
+            Group::load(&ctrl).match_byte(0x00).into_iter().collect(),
+        ),
+        (
+            vec![7],
+            GenericGroup::load(&ctrl)
+                .match_byte(0x00)
+                .into_iter()
+                .collect(),
+        ),
+    ] {
+        assert_eq!(expected, matched);
+    }
+
+    let empty: Vec<usize> = Group::load(&ctrl).match_empty().into_iter().collect();
+    let expected: Vec<usize> = (0..Group::WIDTH)
+        .filter(|&i| ctrl[i] == group::EMPTY)
+        .collect();
+    assert_eq!(empty, expected);
+    let empty: Vec<usize> = GenericGroup::load(&ctrl)
+        .match_empty()
+        .into_iter()
+        .collect();
+    assert_eq!(empty, vec![1, 4, 6]);
+
+    let free = Group::load(&ctrl).match_empty_or_deleted();
+    assert_eq!(free.lowest_set_bit(), Some(1));
+    assert_eq!(
+        free.and(super::group::BitMask::first_lanes(1))
+            .lowest_set_bit(),
+        None
+    );
+    let free = GenericGroup::load(&ctrl).match_empty_or_deleted();
+    assert_eq!(free.into_iter().collect::<Vec<_>>(), vec![1, 2, 4, 6]);
+}
+
+#[test]
+fn test_elastic_hashmap_large_keys() {
+    let mut map = ElasticHashMap::<String, usize>::with_capacity(2048);
+    let keys: Vec<String> = (0..1500).map(|i| format!("{:0>64}", i)).collect();
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(map.insert(key.clone(), i), None);
+    }
+    for (i, key) in keys.iter().enumerate() {
+        assert_eq!(map.get(key.as_str()), Some(&i));
+    }
+    for i in (0..1500).step_by(3) {
+        assert_eq!(map.remove(keys[i].as_str()), Some(i));
+    }
+    for (i, key) in keys.iter().enumerate() {
+        let expected = if i % 3 == 0 { None } else { Some(&i) };
+        assert_eq!(map.get(key.as_str()), expected);
+    }
+    assert_eq!(map.get(format!("{:0>64}", 1500).as_str()), None);
+}
+
+#[test]
+fn test_elastic_hashmap_cached_hashes() {
+    use std::cell::Cell;
+    use std::hash::{Hash, Hasher};
+
+    thread_local! {
+        static HASH_CALLS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[derive(PartialEq, Eq, Clone, Debug)]
+    struct CountingKey(u32);
+
+    impl Hash for CountingKey {
+        fn hash<S: Hasher>(&self, state: &mut S) {
+            HASH_CALLS.with(|calls| calls.set(calls.get() + 1));
+            self.0.hash(state);
+        }
+    }
+
+    let mut map = ElasticHashMap::<CountingKey, u32, CachedHashes>::with_capacity(256);
+    for i in 0..200 {
+        assert_eq!(map.insert(CountingKey(i), i), None);
+    }
+    for i in 0..100 {
+        assert_eq!(map.remove(&CountingKey(i)), Some(i));
+    }
+
+    HASH_CALLS.with(|calls| calls.set(0));
+    map.compact();
+    assert_eq!(HASH_CALLS.with(Cell::get), 0);
+
+    assert_eq!(map.len(), 100);
+    for i in 0..200 {
+        let expected = if i < 100 { None } else { Some(&i) };
+        assert_eq!(map.get(&CountingKey(i)), expected);
+    }
+
+    // the space of removed entries can be used again
+    for i in 200..300 {
+        assert_eq!(map.insert(CountingKey(i), i), None);
+    }
+    assert_eq!(map.len(), 200);
+}
+
+#[test]
+fn test_elastic_hashmap_compact() {
+    let mut map = ElasticHashMap::<i32, i32>::with_capacity(64);
+    for round in 0..10 {
+        for i in 0..40 {
+            map.insert(i, i + round);
+        }
+        for i in 0..40 {
+            assert_eq!(map.remove(&i), Some(i + round));
+        }
+        map.compact();
+        assert!(map.is_empty());
+    }
+}
+
+#[test]
+fn test_elastic_hashmap_churn() {
+    // without reusing tombstones early, removed entries keep taking up the
+    // batch schedule; once it is used up the table is rebuilt in place
+    // rather than doubled, since most of its load is tombstones
+    let mut map: ElasticHashMap<i32, i32> = ElasticHashMapBuilder::new()
+        .capacity(1000)
+        .tombstone_scan(0)
+        .build()
+        .unwrap();
+    let slots = map.size();
+    for round in 0..50 {
+        let keys = round * 400..(round + 1) * 400;
+        for i in keys.clone() {
+            map.insert(i, i);
+        }
+        // keep the last 50 keys until the next round
+        for i in (keys.start - 50).max(0)..keys.end - 50 {
+            assert_eq!(map.remove(&i), Some(i));
+        }
+    }
+    assert_eq!(map.size(), slots);
+    assert_eq!(map.data().len(), slots);
+    let occupied = map
+        .data()
+        .iter()
+        .filter(|slot| matches!(slot, EntryState::Occupied(_)))
+        .count();
+    assert_eq!(occupied, 50);
+    for i in 19950..20000 {
+        assert_eq!(map.get(&i), Some(&i));
+    }
+}
+
+#[test]
+fn test_elastic_hashmap_get_many() {
+    let mut map = ElasticHashMap::<String, usize>::with_capacity(1024);
+    for i in 0..500 {
+        map.insert(i.to_string(), i);
+    }
+
+    let keys: Vec<String> = (250..750).map(|i| i.to_string()).collect();
+    let refs: Vec<&str> = keys.iter().map(String::as_str).collect();
+    let found = map.get_many(&refs);
+    assert_eq!(found.len(), refs.len());
+    for (key, value) in refs.iter().zip(found) {
+        assert_eq!(value, map.get(*key));
+    }
+    assert!(map.get_many::<str>(&[]).is_empty());
+}
+
+#[test]
+fn test_elastic_hashmap_insert_batch() {
+    let mut map = ElasticHashMap::<i32, i32>::with_capacity(1024);
+    map.insert(3, 0);
+
+    // duplicates inside one batch and keys already in the map
+    let pairs = (0..400).map(|i| (i % 300, i));
+    map.insert_batch(pairs);
+
+    assert_eq!(map.len(), 300);
+    for i in 0..300 {
+        let expected = if i < 100 { i + 300 } else { i };
+        assert_eq!(map.get(&i), Some(&expected));
+    }
+
+    let (key, value) = map.insert_unique_unchecked(1000, 1);
+    assert_eq!((*key, *value), (1000, 1));
+    *value = 2;
+    assert_eq!(map.get(&1000), Some(&2));
+    assert_eq!(map.len(), 301);
+}
+
+#[test]
+fn test_elastic_hashmap_insert_batch_grows() {
+    // the table grows several times inside single chunks, walks taken
+    // before a growth must not be reused
+    let mut map = ElasticHashMap::<i32, i32>::with_capacity(1);
+    let slots = map.get_underlying_size();
+    map.insert_batch((0..1000).map(|i| (i, i * 2)));
+    assert!(map.get_underlying_size() >= slots * 8);
+    assert_eq!(map.len(), 1000);
+    map.validate().unwrap();
+    for i in 0..1000 {
+        assert_eq!(map.get(&i), Some(&(i * 2)));
+    }
+
+    for delta_factor in 1..=6 {
+        let mut map: ElasticHashMap<u64, u64> = ElasticHashMapBuilder::new()
+            .capacity(3)
+            .delta_factor(delta_factor)
+            .build()
+            .unwrap();
+        map.insert_batch((0..500).map(|i| (i, i)));
+        map.validate().unwrap();
+        assert_eq!(map.len(), 500);
+        assert!((0..500).all(|i| map.get(&i) == Some(&i)));
+    }
+}
+
+#[test]
+fn test_elastic_hash_table() {
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+
+    use super::table::Entry;
+
+    // rows indexed by a key derived from the value
+    #[derive(Debug, PartialEq)]
+    struct Row {
+        id: u32,
+        name: String,
+    }
+
+    fn hash_id(id: u32) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        hasher.finish()
+    }
+    let hasher = |row: &Row| hash_id(row.id);
+
+    let mut table = ElasticHashTable::<Row>::with_capacity(16);
+    // grows past the initial capacity
+    for id in 0..100 {
+        let name = format!("row {}", id);
+        table.insert_unique(hash_id(id), Row { id, name }, hasher);
+    }
+    assert_eq!(table.len(), 100);
+    assert!(table.get_underlying_size() > 16);
+
+    for id in 0..100 {
+        let row = table.find(hash_id(id), |row| row.id == id).unwrap();
+        assert_eq!(row.name, format!("row {}", id));
+    }
+    assert!(table.find(hash_id(100), |row| row.id == 100).is_none());
+
+    table
+        .find_mut(hash_id(7), |row| row.id == 7)
+        .unwrap()
+        .name
+        .push('!');
+    assert_eq!(
+        table.find(hash_id(7), |row| row.id == 7).unwrap().name,
+        "row 7!"
+    );
+
+    let removed = table.remove(hash_id(3), |row| row.id == 3).unwrap();
+    assert_eq!(removed.id, 3);
+    assert!(table.remove(hash_id(3), |row| row.id == 3).is_none());
+
+    match table.find_entry(hash_id(5), |row| row.id == 5) {
+        Ok(entry) => assert_eq!(entry.remove().id, 5),
+        Err(_) => panic!("row 5 should be present"),
+    }
+    assert!(table.find_entry(hash_id(5), |row| row.id == 5).is_err());
+    assert_eq!(table.len(), 98);
+
+    match table.entry(hash_id(3), |row| row.id == 3, hasher) {
+        Entry::Vacant(entry) => {
+            let name = String::from("again");
+            entry.insert(Row { id: 3, name });
+        }
+        Entry::Occupied(_) => panic!("row 3 was removed"),
+    }
+    match table.entry(hash_id(3), |row| row.id == 3, hasher) {
+        Entry::Occupied(entry) => assert_eq!(entry.get().name, "again"),
+        Entry::Vacant(_) => panic!("row 3 was inserted"),
+    }
+
+    table.compact(hasher);
+    assert_eq!(table.len(), 99);
+    assert_eq!(table.iter().count(), 99);
+    let mut ids: Vec<u32> = table.into_iter().map(|row| row.id).collect();
+    ids.sort();
+    assert_eq!(ids, (0..100).filter(|&id| id != 5).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_elastic_hashmap_equivalent() {
+    use std::hash::{Hash, Hasher};
+
+    // hashes like `(String, u32)`
+    struct Pair<'a>(&'a str, u32);
+
+    impl Hash for Pair<'_> {
+        fn hash<S: Hasher>(&self, state: &mut S) {
+            self.0.hash(state);
+            self.1.hash(state);
+        }
+    }
+
+    impl Equivalent<(String, u32)> for Pair<'_> {
+        fn equivalent(&self, key: &(String, u32)) -> bool {
+            self.0 == key.0 && self.1 == key.1
+        }
+    }
+
+    let mut map = ElasticHashMap::<(String, u32), u32>::with_capacity(64);
+    for i in 0..20 {
+        map.insert((format!("key {}", i), i), i);
+    }
+
+    let name = String::from("key 3");
+    assert_eq!(map.get(&Pair(&name, 3)), Some(&3));
+    assert!(!map.contains_key(&Pair(&name, 4)));
+    *map.get_mut(&Pair(&name, 3)).unwrap() += 100;
+    assert_eq!(map.remove(&Pair(&name, 3)), Some(103));
+    assert!(!map.contains_key(&Pair(&name, 3)));
+
+    match map.entry_ref(&Pair("key 5", 5)) {
+        EntryRef::Occupied(entry) => {
+            assert_eq!(entry.remove_entry(), ((String::from("key 5"), 5), 5))
+        }
+        EntryRef::Vacant(_) => panic!("key 5 should be present"),
+    }
+    assert_eq!(map.len(), 18);
+}
+
+#[test]
+fn test_elastic_hashmap_entry() {
+    let mut map = ElasticHashMap::<String, usize>::with_capacity(16);
+    let words = "a b c a b a d e f g h i j k l m n o p q r s t a";
+    for word in words.split(' ') {
+        *map.entry(word.to_string()).or_insert(0) += 1;
+    }
+    assert_eq!(map.get("a"), Some(&4));
+    assert_eq!(map.get("b"), Some(&2));
+    assert_eq!(map.len(), 20);
+
+    map.entry("a".to_string()).and_modify(|count| *count = 0);
+    assert_eq!(map["a"], 0);
+
+    // the owned key is only created on insert
+    *map.entry_ref("zz").or_default() += 7;
+    *map.entry_ref("zz").or_default() += 1;
+    assert_eq!(map.get("zz"), Some(&8));
+
+    match map.entry("b".to_string()) {
+        Entry::Occupied(mut entry) => {
+            assert_eq!(entry.key(), "b");
+            assert_eq!(entry.insert(10), 2);
+            assert_eq!(entry.remove(), 10);
+        }
+        Entry::Vacant(_) => panic!("b should be present"),
+    }
+    match map.entry("b".to_string()) {
+        Entry::Vacant(entry) => {
+            assert_eq!(entry.key(), "b");
+            assert_eq!(*entry.insert(3), 3);
+        }
+        Entry::Occupied(_) => panic!("b was removed"),
+    }
+    assert_eq!(map.get("b"), Some(&3));
+    assert_eq!(map.len(), 21);
+}
+
+#[test]
+fn test_elastic_hashmap_capacity() {
+    use super::group::Group;
+
+    // ⌈n / (1 - δ)⌉ = 1143 slots, in whole groups
+    assert_eq!(
+        ElasticHashing::slots_for(1000, 3),
+        1143usize.next_multiple_of(Group::WIDTH)
+    );
+    assert_eq!(ElasticHashing::slots_for(896, 3), 1024);
+    assert_eq!(ElasticHashing::slots_for(0, 3), 1);
+    assert_eq!(ElasticHashing::slots_for(7, 3), 8);
+
+    for n in [1, 7, 100, 896, 1000, 1025, 5000, 100_000] {
+        let mut map = ElasticHashing::with_capacity(n);
+        assert!(map.capacity() >= n);
+        let slots = map.get_underlying_size();
+        // never more than a group beyond what δ = 1/8 needs
+        let needed = (n as f64 * 8.0 / 7.0).ceil() as usize;
+        assert!(
+            slots < needed + Group::WIDTH,
+            "{} slots for {} elements",
+            slots,
+            n
+        );
+        for i in 0..n as i32 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.get_underlying_size(), slots, "grew with {} elements", n);
+    }
+
+    let mut map = ElasticHashing::with_capacity(16);
+    for i in 0..16 {
+        map.insert(i, i);
+    }
+    map.reserve(1000);
+    assert!(map.capacity() >= 1016);
+    let slots = map.get_underlying_size();
+    for i in 16..1016 {
+        map.insert(i, i);
+    }
+    assert_eq!(map.get_underlying_size(), slots);
+
+    for i in 100..1016 {
+        map.remove(&i);
+    }
+    map.shrink_to(500);
+    assert!(map.capacity() >= 500);
+    assert!(map.get_underlying_size() < slots);
+    map.shrink_to_fit();
+    assert_eq!(map.get_underlying_size(), ElasticHashing::slots_for(100, 3));
+    assert_eq!(map.len(), 100);
+    for i in 0..1016 {
+        assert_eq!(map.get(&i), if i < 100 { Some(&i) } else { None });
+    }
+}
+
+#[test]
+fn test_elastic_hashmap_builder() {
+    use std::collections::hash_map::RandomState;
+
+    let map: ElasticHashing = ElasticHashMapBuilder::new().capacity(1000).build().unwrap();
+    let default = ElasticHashing::with_capacity(1000);
+    assert_eq!(map.get_underlying_size(), default.get_underlying_size());
+    assert_eq!(map.bucket_count(), default.bucket_count());
+    assert_eq!(map.capacity(), default.capacity());
+
+    let builders = [
+        ElasticHashMapBuilder::new()
+            .batch_fill(0.5)
+            .probe_constant(10.0),
+        ElasticHashMapBuilder::new()
+            .delta_factor(1)
+            .tombstone_scan(0),
+        ElasticHashMapBuilder::new().bucket_ratio(0.25),
+        ElasticHashMapBuilder::new().bucket_ratio(0.75),
+        ElasticHashMapBuilder::new().probe_strategy(probe::ProbeStrategy::Linear),
+        ElasticHashMapBuilder::new().probe_strategy(probe::ProbeStrategy::Quadratic),
+        ElasticHashMapBuilder::new().probe_strategy(probe::ProbeStrategy::DoubleHash),
+    ];
+    for builder in builders {
+        let mut map: ElasticHashMap<i32, i32, NoHashCache, RandomState> = builder
+            .clone()
+            .capacity(700)
+            .hasher(RandomState::new())
+            .build()
+            .unwrap();
+        let slots = map.get_underlying_size();
+        for i in 0..700 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.get_underlying_size(), slots, "{:?}", builder);
+        for i in 0..350 {
+            assert_eq!(map.remove(&i), Some(i));
+        }
+        for i in 0..1500 {
+            map.insert(i, -i);
+        }
+        for i in 0..2000 {
+            let expected = if i < 1500 { Some(-i) } else { None };
+            assert_eq!(map.get(&i).copied(), expected, "{:?}", builder);
+        }
+    }
+
+    let table = ElasticHashMapBuilder::new()
+        .bucket_ratio(0.75)
+        .build_table::<u32, NoHashCache>()
+        .unwrap();
+    let sizes: Vec<usize> = (0..3).map(|i| table.get_bucket(i).len()).collect();
+    assert_eq!(sizes, [8, 6, 5]);
+
+    let errors = [
+        (
+            ElasticHashMapBuilder::new().delta_factor(0),
+            BuildError::InvalidDelta(1.0),
+        ),
+        (
+            ElasticHashMapBuilder::new().batch_fill(0.95),
+            BuildError::InvalidBatchFill(0.95),
+        ),
+        (
+            ElasticHashMapBuilder::new().batch_fill(0.0),
+            BuildError::InvalidBatchFill(0.0),
+        ),
+        (
+            ElasticHashMapBuilder::new().probe_constant(-1.0),
+            BuildError::InvalidProbeConstant(-1.0),
+        ),
+        (
+            ElasticHashMapBuilder::new().bucket_ratio(1.0),
+            BuildError::InvalidBucketRatio(1.0),
+        ),
+        (
+            ElasticHashMapBuilder::new()
+                .bucket_ratio(0.75)
+                .probe_strategy(probe::ProbeStrategy::Quadratic),
+            BuildError::IncompatibleProbeStrategy(probe::ProbeStrategy::Quadratic),
+        ),
+    ];
+    for (builder, error) in errors {
+        assert_eq!(builder.build::<i32, i32, NoHashCache>().unwrap_err(), error);
+    }
+}
+
+#[test]
+fn test_elastic_hashmap_float_delta() {
+    for delta in [0.3, 0.05, 0.03] {
+        for capacity in [10, 100, 1000, 3000] {
+            let mut map = ElasticHashing::with_capacity_and_delta(capacity, delta).unwrap();
+            let slots = map.get_underlying_size();
+            assert_eq!(map.capacity(), (slots as f64 * (1.0 - delta)) as usize);
+            assert!(map.capacity() >= capacity);
+            // the batch invariants are asserted on every insert
+            for i in 0..map.capacity() as i32 {
+                map.insert(i, i);
+            }
+            assert_eq!(map.get_underlying_size(), slots, "δ = {}", delta);
+            for i in 0..map.capacity() as i32 {
+                assert_eq!(map.get(&i), Some(&i));
+            }
+        }
+    }
+
+    let map: ElasticHashing = ElasticHashMapBuilder::new()
+        .delta_ratio(1, 20)
+        .capacity(100)
+        .build()
+        .unwrap();
+    assert!((100..100 + super::group::Group::WIDTH).contains(&map.capacity()));
+
+    for delta in [0.0, 1.0, -0.5, f64::NAN] {
+        let error = ElasticHashing::with_capacity_and_delta(100, delta).unwrap_err();
+        assert!(matches!(error, BuildError::InvalidDelta(_)));
+    }
+    let error = ElasticHashMapBuilder::new()
+        .delta_ratio(1, 0)
+        .build::<i32, i32, NoHashCache>()
+        .unwrap_err();
+    assert_eq!(error, BuildError::InvalidDelta(f64::INFINITY));
+}
+
+#[test]
+fn test_elastic_hashmap_geometry() {
+    fn bucket_sizes(map: &ElasticHashing) -> Vec<usize> {
+        (0..map.bucket_count())
+            .map(|i| map.get_bucket(i).len())
+            .collect()
+    }
+
+    let map: ElasticHashing = ElasticHashMapBuilder::new().slots(600).build().unwrap();
+    assert_eq!(map.get_underlying_size(), 600);
+    assert_eq!(bucket_sizes(&map)[..4], [300, 150, 75, 38]);
+
+    let map: ElasticHashing = ElasticHashMapBuilder::new()
+        .slots(64)
+        .min_bucket_size(4)
+        .build()
+        .unwrap();
+    assert_eq!(bucket_sizes(&map), [32, 16, 8, 4, 4]);
+
+    let map: ElasticHashing = ElasticHashMapBuilder::new()
+        .slots(64)
+        .bucket_count(3)
+        .build()
+        .unwrap();
+    assert_eq!(bucket_sizes(&map), [32, 16, 16]);
+
+    let map: ElasticHashing = ElasticHashMapBuilder::new()
+        .capacity(1000)
+        .power_of_two_size(true)
+        .build()
+        .unwrap();
+    assert_eq!(map.get_underlying_size(), 2048);
+
+    let map: ElasticHashing = ElasticHashMapBuilder::new()
+        .capacity(1000)
+        .probe_strategy(probe::ProbeStrategy::Quadratic)
+        .build()
+        .unwrap();
+    assert_eq!(map.get_underlying_size(), 2048);
+
+    let builders = [
+        ElasticHashMapBuilder::new().slots(600_000 / 64),
+        ElasticHashMapBuilder::new().slots(1000).min_bucket_size(64),
+        ElasticHashMapBuilder::new().slots(1000).bucket_count(1),
+        ElasticHashMapBuilder::new().slots(1000).bucket_count(2),
+        ElasticHashMapBuilder::new()
+            .slots(1000)
+            .bucket_ratio(0.3)
+            .min_bucket_size(16),
+        ElasticHashMapBuilder::new()
+            .capacity(500)
+            .bucket_ratio(0.7)
+            .bucket_count(4),
+    ];
+    for builder in builders {
+        let mut map: ElasticHashing = builder.clone().build().unwrap();
+        let slots = map.get_underlying_size();
+        // the batch invariants are asserted on every insert
+        for i in 0..map.capacity() as i32 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.get_underlying_size(), slots, "{:?}", builder);
+        let n = map.len() as i32;
+        for i in n..2 * n {
+            map.insert(i, i);
+        }
+        assert_eq!(map.get_underlying_size(), 2 * slots, "{:?}", builder);
+        for i in 0..3 * n {
+            let expected = if i < 2 * n { Some(&i) } else { None };
+            assert_eq!(map.get(&i), expected, "{:?}", builder);
+        }
+    }
+
+    let errors = [
+        (ElasticHashMapBuilder::new().slots(0), BuildError::ZeroSlots),
+        (
+            ElasticHashMapBuilder::new().min_bucket_size(0),
+            BuildError::InvalidMinBucketSize,
+        ),
+        (
+            ElasticHashMapBuilder::new().bucket_count(0),
+            BuildError::InvalidBucketCount,
+        ),
+        (
+            ElasticHashMapBuilder::new()
+                .slots(600)
+                .probe_strategy(probe::ProbeStrategy::DoubleHash),
+            BuildError::IncompatibleProbeStrategy(probe::ProbeStrategy::DoubleHash),
+        ),
+    ];
+    for (builder, error) in errors {
+        assert_eq!(builder.build::<i32, i32, NoHashCache>().unwrap_err(), error);
+    }
+}
+
+#[test]
+pub(crate) fn test_elastic_hashmap_bucket_independence() {
+    const BINS: usize = 8;
+    const KEYS: i32 = 20000;
+    for strategy in [probe::ProbeStrategy::Uniform, probe::ProbeStrategy::Linear] {
+        let map: ElasticHashing = ElasticHashMapBuilder::new()
+            .slots(1024)
+            .probe_strategy(strategy)
+            .build()
+            .unwrap();
+        let len_1 = map.get_bucket(0).len();
+        let len_2 = map.get_bucket(1).len();
+
+        // first slot of every key in the first two buckets
+        let mut table = [[0usize; BINS]; BINS];
+        let groups_2 = len_2 / group::Group::WIDTH;
+        let mut same_group = 0;
+        for key in 0..KEYS {
+            let pos_1 = map.sequence(&key, 1).probe(1) % len_1;
+            let pos_2 = map.sequence(&key, 2).probe(1) % len_2;
+            table[pos_1 * BINS / len_1][pos_2 * BINS / len_2] += 1;
+            let group_1 = pos_1 / group::Group::WIDTH % groups_2;
+            same_group += (group_1 == pos_2 / group::Group::WIDTH) as usize;
+        }
+
+        // chi-square test of independence, 49 degrees of freedom
+        let rows = table.map(|row| row.iter().sum::<usize>());
+        let cols: Vec<usize> = (0..BINS)
+            .map(|c| table.iter().map(|row| row[c]).sum())
+            .collect();
+        let mut chi_square = 0.0;
+        for r in 0..BINS {
+            for c in 0..BINS {
+                let expected = (rows[r] * cols[c]) as f64 / KEYS as f64;
+                chi_square += (table[r][c] as f64 - expected).powi(2) / expected;
+            }
+        }
+        // critical value at p = 0.001
+        assert!(
+            chi_square < 85.35,
+            "{:?}: chi-square {}",
+            strategy,
+            chi_square
+        );
+
+        // a shared seed would land keys on the same offset in both buckets
+        let rate = same_group as f64 / KEYS as f64;
+        assert!(
+            rate < 2.0 / groups_2 as f64,
+            "{:?}: same group rate {}",
+            strategy,
+            rate
+        );
+    }
+}
+
+#[test]
+pub(crate) fn test_elastic_hashmap_validate() {
+    let builders = [
+        ElasticHashMapBuilder::new().capacity(300),
+        ElasticHashMapBuilder::new().slots(700).bucket_ratio(0.3),
+        ElasticHashMapBuilder::new().capacity(200).delta(0.3),
+    ];
+    for builder in builders {
+        let mut map: ElasticHashMap<i32, i32, CachedHashes> = builder.clone().build().unwrap();
+        assert_eq!(map.validate(), Ok(()));
+        for i in 0..500 {
+            map.insert(i, i);
+            if i % 3 == 0 {
+                map.remove(&(i / 2));
+            }
+            if i % 50 == 0 {
+                assert_eq!(map.validate(), Ok(()), "{:?}", builder);
+            }
+        }
+        map.compact();
+        assert_eq!(map.validate(), Ok(()), "{:?}", builder);
+        map.shrink_to_fit();
+        assert_eq!(map.validate(), Ok(()), "{:?}", builder);
+        map.clear();
+        assert_eq!(map.validate(), Ok(()), "{:?}", builder);
+    }
+
+    // a key whose hash changes after insertion cannot be found any more
+    #[derive(Clone, PartialEq, Eq)]
+    struct Key(std::rc::Rc<std::cell::Cell<i32>>);
+    impl std::hash::Hash for Key {
+        fn hash<S: std::hash::Hasher>(&self, state: &mut S) {
+            self.0.get().hash(state);
+        }
+    }
+    let mut map = ElasticHashMap::<Key, i32>::with_capacity(16);
+    let key = Key(Default::default());
+    map.insert(key.clone(), 0);
+    assert_eq!(map.validate(), Ok(()));
+    key.0.set(1);
+    let err = map.validate().unwrap_err();
+    assert!(
+        matches!(err, InvariantViolation::Unreachable { .. }),
+        "{}",
+        err
+    );
+
+    let mut map = ElasticHashing::with_capacity(16);
+    map.insert(1, 1);
+    // paranoid maps catch the duplicate as soon as it is inserted
+    #[cfg(feature = "paranoid")]
+    {
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            map.insert_unique_unchecked(1, 2);
+        }));
+        assert!(result.is_err());
+    }
+    #[cfg(not(feature = "paranoid"))]
+    {
+        map.insert_unique_unchecked(1, 2);
+        let err = map.validate().unwrap_err();
+        assert!(
+            matches!(err, InvariantViolation::Duplicate { .. }),
+            "{}",
+            err
+        );
+    }
+}
+
+#[cfg(feature = "paranoid")]
+#[test]
+fn test_paranoid_full_check() {
+    // a key whose hash changes is not touched by later mutations, only the
+    // periodic full check finds it
+    #[derive(Clone, PartialEq, Eq)]
+    struct Key(std::rc::Rc<std::cell::Cell<i32>>, i32);
+    impl std::hash::Hash for Key {
+        fn hash<S: std::hash::Hasher>(&self, state: &mut S) {
+            (self.0.get(), self.1).hash(state);
+        }
+    }
+    let mut map = ElasticHashMap::<Key, i32>::with_capacity(16);
+    let shift = std::rc::Rc::new(std::cell::Cell::new(0));
+    map.insert(Key(shift.clone(), 0), 0);
+    shift.set(1);
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        for i in 1..100 {
+            map.insert(Key(Default::default(), i), i);
+            map.remove(&Key(Default::default(), i));
+        }
+    }));
+    assert!(result.is_err());
+}
+
+#[test]
+pub(crate) fn test_elastic_hashmap_batch_status() {
+    let mut map = ElasticHashing::with_capacity(500);
+    let status = map.batch_status();
+    assert_eq!((status.batch, status.batch_count), (0, 0));
+    assert_eq!(status.batch_max.len(), map.bucket_count() + 1);
+    assert_eq!(status.next_insert, InsertBranch::Only { bucket: 0 });
+    assert!(status
+        .buckets
+        .iter()
+        .all(|b| b.load == 0 && b.epsilon == 1.0));
+
+    let mut branches = Vec::new();
+    for i in 0..map.capacity() as i32 {
+        let status = map.batch_status();
+        assert_eq!(
+            status.buckets.iter().map(|b| b.load).sum::<usize>(),
+            status.batch_max[..status.batch].iter().sum::<usize>() + status.batch_count
+        );
+        assert!(status.batch_count < status.batch_max[status.batch]);
+        for (idx, bucket) in status.buckets.iter().enumerate() {
+            assert_eq!(bucket.capacity, map.get_bucket(idx).len());
+            assert_eq!(
+                bucket.epsilon,
+                1.0 - bucket.load as f64 / bucket.capacity as f64
+            );
+        }
+        // the branch only uses the buckets of the running batch
+        match status.next_insert {
+            InsertBranch::Limited { bucket, limit } => {
+                assert_eq!(bucket + 1, status.batch);
+                assert!(limit > 0);
+            }
+            InsertBranch::Next { bucket } => assert_eq!(bucket, status.batch),
+            InsertBranch::Only { bucket } => {
+                assert!(bucket == status.batch || bucket + 1 == status.batch)
+            }
+            InsertBranch::Grow => unreachable!(),
+        }
+        if branches.last() != Some(&status.next_insert) {
+            branches.push(status.next_insert);
+        }
+        map.insert(i, i);
+    }
+    assert!(branches
+        .iter()
+        .any(|b| matches!(b, InsertBranch::Limited { .. })));
+    assert!(branches
+        .iter()
+        .any(|b| matches!(b, InsertBranch::Next { .. })));
+    assert_eq!(map.batch_status().next_insert, InsertBranch::Grow);
+
+    map.remove(&0);
+    assert_eq!(
+        map.batch_status()
+            .buckets
+            .iter()
+            .map(|b| b.tombstones)
+            .sum::<usize>(),
+        1
+    );
+}
+
+#[test]
+pub(crate) fn test_elastic_hashmap_probe_stats() {
+    let histogram = ProbeHistogram::new();
+    assert_eq!((histogram.percentile(0.5), histogram.mean()), (0, 0.0));
+    for probes in [0, 1, 1, 2, 3, 5, 9, 40] {
+        histogram.record(probes);
+    }
+    assert_eq!(histogram.count(), 8);
+    assert_eq!(histogram.mean(), 61.0 / 8.0);
+    assert_eq!(histogram.percentile(0.5), 3);
+    assert_eq!(histogram.percentile(0.99), 40);
+    assert_eq!(histogram.max(), 40);
+    assert_eq!(
+        histogram.bins().collect::<Vec<_>>(),
+        [
+            (1, (0, 0)),
+            (2, (1, 1)),
+            (2, (2, 3)),
+            (1, (4, 7)),
+            (1, (8, 15)),
+            (1, (32, 63))
+        ]
+    );
+    let json = histogram.to_json();
+    assert!(json.starts_with(
+        r#"{"count":8,"mean":7.625,"p50":3,"p99":40,"max":40,"bins":[{"lo":0,"hi":0,"count":1},"#
+    ));
+
+    let mut map: ElasticHashing = ElasticHashMapBuilder::new()
+        .capacity(100)
+        .probe_stats(true)
+        .build()
+        .unwrap();
+    for i in 0..300 {
+        map.insert(i, i);
+    }
+    let stats = map.probe_stats().unwrap();
+    // the stats carry over when the table grows
+    assert_eq!(stats.insert_total().count(), 300);
+    assert_eq!(stats.inserts().len(), map.bucket_count());
+    assert!(stats.insert_total().percentile(0.5) >= 1);
+    // every insert looked its key up first
+    assert_eq!(stats.misses().count(), 300);
+    assert_eq!(stats.hits().count(), 0);
+
+    stats.reset();
+    for i in 0..400 {
+        assert_eq!(map.get(&i).is_some(), i < 300);
+    }
+    let stats = map.probe_stats().unwrap();
+    assert_eq!(stats.hits().count(), 300);
+    assert_eq!(stats.misses().count(), 100);
+    assert!(stats.hits().percentile(0.99) <= stats.hits().max());
+    // the lookups of `validate` are not the caller's
+    map.validate().unwrap();
+    assert_eq!(stats.hits().count(), 300);
+    assert_eq!(stats.insert_total().count(), 0);
+    assert!(stats
+        .to_json()
+        .starts_with(r#"{"inserts":{"total":{"count":0,"#));
+
+    assert!(ElasticHashing::with_capacity(16).probe_stats().is_none());
+}
+
+#[test]
+pub(crate) fn test_elastic_hashmap_explain() {
+    // a small probe constant makes inserts run into the f(ε) limit
+    let mut map: ElasticHashing = ElasticHashMapBuilder::new()
+        .capacity(400)
+        .probe_constant(1.0)
+        .build()
+        .unwrap();
+    let mut limited = false;
+    for i in 0..map.capacity() as i32 {
+        let trace = map.explain(&i);
+        let TraceOutcome::Insert { index, bucket, .. } = trace.outcome else {
+            panic!("{}", trace);
+        };
+        limited |= trace
+            .steps
+            .iter()
+            .any(|step| step.abandoned == Some(AbandonReason::ProbeLimit));
+
+        // the lookup visits (i, j) in phi order, each bucket until abandoned
+        let lookup: Vec<_> = trace
+            .steps
+            .iter()
+            .filter(|step| step.phase == Phase::Lookup)
+            .collect();
+        assert!(lookup.windows(2).all(|pair| ElasticHashing::phi(
+            pair[0].bucket as u32 + 1,
+            pair[0].attempt
+        ) < ElasticHashing::phi(
+            pair[1].bucket as u32 + 1,
+            pair[1].attempt
+        )));
+        for b in 0..map.bucket_count() {
+            let steps: Vec<_> = lookup.iter().filter(|step| step.bucket == b).collect();
+            assert!(steps
+                .iter()
+                .enumerate()
+                .all(|(n, s)| s.attempt == n as u32 + 1));
+            if let Some((last, rest)) = steps.split_last() {
+                assert!(last.abandoned.is_some(), "{}", trace);
+                assert!(rest.iter().all(|step| step.abandoned.is_none()));
+            }
+        }
+
+        // the prediction holds
+        map.insert(i, i);
+        let trace = map.explain(&i);
+        assert_eq!(
+            trace.outcome,
+            TraceOutcome::Found {
+                index,
+                bucket,
+                attempt: trace.steps.last().unwrap().attempt
+            }
+        );
+        assert!(trace.to_string().contains("found in slot"));
+    }
+    assert!(limited);
+
+    let trace = map.explain(&-1);
+    assert_eq!(trace.outcome, TraceOutcome::Grow);
+    assert!(trace.to_string().contains("would grow"));
+
+    map.remove(&0);
+    let trace = map.explain(&0);
+    let TraceOutcome::Insert { index, .. } = trace.outcome else {
+        panic!("{}", trace);
+    };
+    map.insert(0, 0);
+    assert!(
+        matches!(map.explain(&0).outcome, TraceOutcome::Found { index: found, .. } if found == index)
+    );
+}
+
+#[test]
+pub(crate) fn test_elastic_hashmap_layout() {
+    let mut map: ElasticHashing = ElasticHashMapBuilder::new().slots(64).build().unwrap();
+    for i in 0..40 {
+        map.insert(i, i);
+    }
+    for i in 0..10 {
+        map.remove(&i);
+    }
+    let layout = map.layout();
+    assert_eq!(layout.buckets.len(), map.bucket_count());
+    for (i, bucket) in layout.buckets.iter().enumerate() {
+        assert_eq!(bucket.size, map.get_bucket(i).len());
+        let count = |kind| bucket.slots.iter().filter(|&&slot| slot == kind).count();
+        assert_eq!(bucket.tombstones, count(SlotKind::Tombstone));
+        assert_eq!(bucket.load, count(SlotKind::Occupied) + bucket.tombstones);
+    }
+    assert_eq!(
+        layout.buckets.iter().map(|b| b.tombstones).sum::<usize>(),
+        10
+    );
+
+    let ascii = layout.to_ascii(16);
+    assert_eq!(ascii.lines().count(), map.bucket_count());
+    let first = ascii.lines().next().unwrap();
+    assert!(first.starts_with("A_1   size       32 load "), "{}", first);
+    let strip = &first[first.find('|').unwrap() + 1..first.len() - 1];
+    assert_eq!(strip.len(), 16);
+
+    let json = layout.to_json();
+    assert!(json.starts_with(r#"{"buckets":[{"bucket":1,"size":32,"load":"#));
+    assert_eq!(json.matches('x').count(), 10);
+    assert_eq!(json.matches('#').count(), 30);
+
+    let svg = layout.to_svg();
+    assert!(svg.starts_with("<svg") && svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("<text").count(), map.bucket_count());
+
+    let empty = ElasticHashing::with_capacity(16).layout().to_ascii(8);
+    for line in empty.lines() {
+        let strip = &line[line.find('|').unwrap()..];
+        assert!(
+            strip.trim_matches('|').chars().all(|c| c == ' '),
+            "{}",
+            line
+        );
+    }
+}
+
+#[derive(Default)]
+struct CountingObserver {
+    inserts: std::cell::Cell<usize>,
+    hits: std::cell::Cell<usize>,
+    misses: std::cell::Cell<usize>,
+    removes: std::cell::Cell<usize>,
+    reuses: std::cell::Cell<usize>,
+    batches: std::cell::RefCell<Vec<usize>>,
+    resizes: std::cell::RefCell<Vec<(usize, usize)>>,
+    compacts: std::cell::RefCell<Vec<usize>>,
+    current: std::cell::Cell<Option<Operation>>,
+    operations: std::cell::Cell<usize>,
+}
+
+impl CountingObserver {
+    fn assert_inside(&self, operation: Operation) {
+        assert_eq!(self.current.get(), Some(operation));
+    }
+}
+
+impl ElasticObserver for CountingObserver {
+    fn on_begin(&self, operation: Operation) {
+        assert_eq!(self.current.replace(Some(operation)), None);
+        self.operations.set(self.operations.get() + 1);
+    }
+
+    fn on_finish(&self, operation: Operation) {
+        assert_eq!(self.current.take(), Some(operation));
+    }
+
+    fn on_insert(&self, bucket: usize, _attempts: u32) {
+        assert!(bucket < 64);
+        self.assert_inside(Operation::Insert);
+        self.inserts.set(self.inserts.get() + 1);
+    }
+
+    fn on_lookup(&self, found: bool, _probes: u32) {
+        self.assert_inside(Operation::Lookup);
+        let counter = if found { &self.hits } else { &self.misses };
+        counter.set(counter.get() + 1);
+    }
+
+    fn on_remove(&self, _bucket: usize) {
+        self.assert_inside(Operation::Remove);
+        self.removes.set(self.removes.get() + 1);
+    }
+
+    fn on_tombstone_reuse(&self, _bucket: usize) {
+        self.assert_inside(Operation::Insert);
+        self.reuses.set(self.reuses.get() + 1);
+    }
+
+    fn on_batch_advance(&self, batch: usize) {
+        self.assert_inside(Operation::Insert);
+        self.batches.borrow_mut().push(batch);
+    }
+
+    fn on_resize(&self, old_slots: usize, new_slots: usize) {
+        self.assert_inside(Operation::Resize);
+        self.resizes.borrow_mut().push((old_slots, new_slots));
+    }
+
+    fn on_compact(&self, tombstones: usize) {
+        self.assert_inside(Operation::Compact);
+        self.compacts.borrow_mut().push(tombstones);
+    }
+}
+
+#[test]
+pub(crate) fn test_elastic_hashmap_observer() {
+    let mut map: ElasticHashMap<i32, i32, NoHashCache, DefaultHashBuilder, CountingObserver> =
+        ElasticHashMapBuilder::new()
+            .capacity(100)
+            .observer(CountingObserver::default())
+            .build()
+            .unwrap();
+    let slots = map.get_underlying_size();
+    for i in 0..300 {
+        map.insert(i, i);
+    }
+    let observer = map.observer();
+    // moving the values into the grown table is not reported
+    assert_eq!(observer.inserts.get(), 300);
+    assert_eq!(observer.misses.get(), 300);
+    let resizes = observer.resizes.borrow().clone();
+    assert!(!resizes.is_empty());
+    assert_eq!(resizes[0], (slots, slots * 2));
+    for pair in resizes.windows(2) {
+        assert_eq!(pair[0].1, pair[1].0);
+    }
+    // a batch is only ever reported once it started
+    let batches = observer.batches.borrow().clone();
+    assert!(!batches.is_empty());
+    assert!(batches.iter().all(|&batch| batch >= 1));
+
+    for i in 0..300 {
+        assert_eq!(map.get(&i), Some(&i));
+    }
+    assert_eq!(map.observer().hits.get(), 300);
+    map.validate().unwrap();
+    assert_eq!(map.observer().hits.get(), 300);
+
+    for i in 0..20 {
+        map.remove(&i);
+    }
+    assert_eq!(map.observer().removes.get(), 20);
+    for i in 0..20 {
+        map.insert(i, i);
+    }
+    assert!(map.observer().reuses.get() <= 20);
+    for i in 20..30 {
+        map.remove(&i);
+    }
+    let tombstones: usize = map.layout().buckets.iter().map(|b| b.tombstones).sum();
+    map.compact();
+    assert_eq!(*map.observer().compacts.borrow(), [tombstones]);
+    assert!(tombstones >= 10);
+    let observer = map.observer();
+    assert_eq!(observer.inserts.get(), 320);
+    assert_eq!(observer.current.get(), None);
+    // every lookup, insert, remove and rebuild is an operation of its own
+    let hits_and_misses = observer.hits.get() + observer.misses.get();
+    let rebuilds = observer.resizes.borrow().len() + observer.compacts.borrow().len();
+    assert_eq!(
+        observer.operations.get(),
+        hits_and_misses + observer.inserts.get() + observer.removes.get() + rebuilds
+    );
+}
+
+#[cfg(feature = "tracing")]
+#[test]
+fn test_tracing_observer() {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use std::thread::{self, ThreadId};
+    use tracing::span::{Attributes, Id, Record};
+
+    #[derive(Default)]
+    struct SpanLog {
+        spans: u64,
+        entered: HashMap<ThreadId, Vec<u64>>,
+        events: usize,
+    }
+
+    /// Subscriber that checks every event against the span its thread
+    /// entered last
+    struct Collector(Arc<Mutex<SpanLog>>);
+
+    impl tracing::Subscriber for Collector {
+        fn enabled(&self, _metadata: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut log = self.0.lock().unwrap();
+            log.spans += 1;
+            // the map span is created first, every operation is its child
+            if log.spans > 1 {
+                assert_eq!(span.parent().map(Id::into_u64), Some(1));
+            }
+            Id::from_u64(log.spans)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut log = self.0.lock().unwrap();
+            let parent = event.parent().expect("the event has no parent").into_u64();
+            let entered = log.entered.entry(thread::current().id()).or_default();
+            assert_eq!(entered.last(), Some(&parent));
+            log.events += 1;
+        }
+
+        fn enter(&self, span: &Id) {
+            let mut log = self.0.lock().unwrap();
+            let entered = log.entered.entry(thread::current().id()).or_default();
+            // operations of one thread never nest
+            assert!(entered.is_empty());
+            entered.push(span.into_u64());
+        }
+
+        fn exit(&self, span: &Id) {
+            let mut log = self.0.lock().unwrap();
+            let entered = log.entered.entry(thread::current().id()).or_default();
+            assert_eq!(entered.pop(), Some(span.into_u64()));
+        }
+    }
+
+    let log = Arc::new(Mutex::new(SpanLog::default()));
+    let map = tracing::subscriber::with_default(Collector(log.clone()), || {
+        let mut map: ElasticHashMap<i32, i32, NoHashCache, DefaultHashBuilder, TracingObserver> =
+            ElasticHashMapBuilder::new()
+                .capacity(100)
+                .observer(TracingObserver::default())
+                .build()
+                .unwrap();
+        for i in 0..300 {
+            map.insert(i, i);
+        }
+        for i in 0..300 {
+            assert_eq!(map.get(&i), Some(&i));
+        }
+        for i in 0..20 {
+            map.remove(&i);
+        }
+        map.compact();
+        map
+    });
+    assert!(log.lock().unwrap().entered.values().all(Vec::is_empty));
+
+    // lookups from several threads at once each run in a span of their own
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                tracing::subscriber::with_default(Collector(log.clone()), || {
+                    for _ in 0..10 {
+                        for i in 0..300 {
+                            assert_eq!(map.get(&i), (i >= 20).then_some(&i));
+                        }
+                    }
+                });
+            });
+        }
+    });
+    let log = log.lock().unwrap();
+    assert!(log.entered.values().all(Vec::is_empty));
+    // the map span and one span per operation
+    assert!(log.spans > 900 + 4 * 3000);
+    assert!(log.events >= 900 + 4 * 3000);
+}
+
+#[test]
+fn test_elastic_simulator_replay() {
+    use std::hash::BuildHasher;
+
+    // the simulator has its own copy of the slot choice, replaying the same
+    // keys through both must put every one of them in the same slot
+    let builders = [
+        ElasticHashMapBuilder::new().capacity(3000),
+        ElasticHashMapBuilder::new()
+            .slots(2000)
+            .delta_factor(4)
+            .bucket_ratio(0.3)
+            .probe_strategy(probe::ProbeStrategy::Linear)
+            .prng(probe::Prng::Pcg),
+        ElasticHashMapBuilder::new()
+            .capacity(2500)
+            .probe_strategy(probe::ProbeStrategy::Quadratic)
+            .prng(probe::Prng::SplitMix64),
+        ElasticHashMapBuilder::new()
+            .capacity(1500)
+            .probe_strategy(probe::ProbeStrategy::DoubleHash)
+            .prng(probe::Prng::XorShiftStar),
+    ];
+    for builder in builders {
+        for seed in 0..3 {
+            let hash_builder = SeededState::new(seed);
+            let mut table: ElasticHashTable<u64> = builder.build_table().unwrap();
+            let mut simulator = builder.build_simulator().unwrap();
+            let keys = Keys::sequential(seed << 32).take(table.capacity());
+            for key in keys {
+                let hash = hash_builder.hash_one(key);
+                let placement = simulator.insert(hash).unwrap();
+                table.insert_unique(hash, key, |&v| hash_builder.hash_one(v));
+                let TraceOutcome::Found {
+                    index,
+                    bucket,
+                    attempt,
+                } = table.explain(hash, |&v| v == key).outcome
+                else {
+                    panic!("{} is missing", key);
+                };
+                assert_eq!(
+                    (placement.index, placement.bucket, placement.attempt),
+                    (index, bucket, attempt),
+                    "{:?}, seed {}, key {}",
+                    builder,
+                    seed,
+                    key
+                );
+            }
+            assert_eq!(simulator.insert(0), None);
+        }
+    }
+}
+
+#[test]
+pub(crate) fn test_elastic_simulator() {
+    let builders = [
+        ElasticHashMapBuilder::new().capacity(5000),
+        ElasticHashMapBuilder::new()
+            .slots(3000)
+            .delta_factor(5)
+            .probe_strategy(probe::ProbeStrategy::Linear),
+        ElasticHashMapBuilder::new()
+            .slots(4096)
+            .delta_factor(2)
+            .probe_constant(1.0),
+    ];
+    for builder in builders {
+        let mut table: ElasticHashTable<u64> = builder.build_table().unwrap();
+        let mut simulator = builder.build_simulator().unwrap();
+        assert_eq!(simulator.slots(), table.get_underlying_size());
+        assert_eq!(simulator.bucket_count(), table.bucket_count());
+
+        let hashes = (0..table.capacity() as u64).map(|k| k.wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        for hash in hashes.clone() {
+            let placement = simulator.insert(hash).unwrap();
+            table.insert_unique(hash, hash, |&v| v);
+            assert!(placement.probes >= 1);
+            assert!(placement.attempt <= placement.probes);
+            assert_eq!(table.batch_status(), simulator.batch_status());
+        }
+        // every hash landed in the same slot
+        let mut idx = 0;
+        for bucket in table.layout().buckets {
+            for slot in bucket.slots {
+                assert_eq!(slot == SlotKind::Occupied, simulator.is_occupied(idx));
+                idx += 1;
+            }
+        }
+        for (i, hash) in hashes.enumerate().step_by(97) {
+            let trace = table.explain(hash, |&v| v == hash);
+            let TraceOutcome::Found { index, .. } = trace.outcome else {
+                panic!("{} is missing", i);
+            };
+            assert!(simulator.is_occupied(index));
+        }
+
+        assert_eq!(simulator.len(), simulator.capacity());
+        assert_eq!(simulator.insert(1), None);
+        assert_eq!(simulator.batch_status().next_insert, InsertBranch::Grow);
+        let stats = simulator.probe_stats();
+        assert_eq!(stats.insert_total().count(), simulator.len() as u64);
+        assert_eq!(stats.misses().count(), 0);
+    }
+
+    let mut simulator = ElasticHashMapBuilder::new()
+        .slots(1 << 20)
+        .delta_factor(10)
+        .build_simulator()
+        .unwrap();
+    let inserted = simulator.fill((0..).map(|k: u64| k.wrapping_mul(0xBF58_476D_1CE4_E5B9)));
+    assert_eq!(inserted, simulator.capacity());
+    assert_eq!(
+        (0..simulator.bucket_count())
+            .map(|i| simulator.bucket_load(i))
+            .sum::<usize>(),
+        inserted
+    );
+    assert!(simulator.probe_stats().insert_total().mean() < 4.0);
+}
+
+#[test]
+pub(crate) fn test_seeded_hasher() {
+    use std::hash::BuildHasher;
+    let state = SeededState::new(42);
+    assert_eq!(state.hash_one(7u64), SeededState::new(42).hash_one(7u64));
+    assert_ne!(state.hash_one(7u64), SeededState::new(43).hash_one(7u64));
+    assert_ne!(state.hash_one(7u64), state.hash_one(8u64));
+    assert_ne!(state.hash_one(("ab", "c")), state.hash_one(("a", "bc")));
+    // the same across platforms and releases, traces depend on it
+    assert_eq!(state.hash_one(7u64), 0x26c7_f0c7_7ae1_08d7);
+
+    let mut map: ElasticHashMap<u64, u64, NoHashCache, SeededState> =
+        ElasticHashMap::with_hasher(SeededState::new(7));
+    for i in 0..1000 {
+        map.insert(i, i);
+    }
+    map.validate().unwrap();
+}
+
+#[test]
+pub(crate) fn test_record_replay() {
+    use rand::Rng;
+    use std::{collections::HashMap, hash::BuildHasher};
+
+    let mut rng = test_rng();
+    let map: ElasticHashMap<u32, String, NoHashCache, SeededState> =
+        ElasticHashMap::with_hasher(SeededState::new(1));
+    let mut recorder = Recorder::new(map, Vec::new());
+    for _ in 0..2000 {
+        let key = rng.random_range(0..300);
+        match rng.random_range(0..10) {
+            0..=3 => {
+                recorder.insert(key, format!("v\"{}\n", key * 3));
+            }
+            4..=6 => {
+                recorder.get(&key);
+            }
+            7 | 8 => {
+                recorder.remove(&key);
+            }
+            _ => match rng.random_range(0..4) {
+                0 => recorder.compact(),
+                1 => recorder.reserve(50),
+                2 => recorder.shrink_to_fit(),
+                _ => recorder.clear(),
+            },
+        }
+    }
+    recorder.flush().unwrap();
+    let (map, trace) = recorder.into_parts();
+    let events = read_trace::<u32, String>(&trace[..])
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(events.len(), 2001);
+    assert_eq!(events.last().unwrap().len, map.len());
+    let fresh: ElasticHashMap<u32, String, NoHashCache, SeededState> =
+        ElasticHashMap::with_hasher(SeededState::new(1));
+    let header = TraceHeader::of(&fresh);
+    assert_eq!(header.seed, Some(1));
+    assert_eq!(header.group_width, group::Group::WIDTH);
+    assert_eq!(events[0].op, TraceOp::Header(header));
+    for event in &events {
+        assert_eq!(
+            TraceEvent::parse(&event.to_json()).as_ref(),
+            Ok(event),
+            "{}",
+            event.to_json()
+        );
+        if let TraceOp::Insert { key, .. } = event.op {
+            assert_eq!(event.hash, Some(map.hasher().hash_one(key)));
+        }
+    }
+
+    // replays against the same map, with another hasher, or another map
+    let mut same: ElasticHashMap<u32, String, NoHashCache, SeededState> =
+        ElasticHashMap::with_hasher(SeededState::new(1));
+    assert_eq!(replay(&trace[..], &mut same).unwrap(), 2001);
+    assert_eq!(same.batch_status(), map.batch_status());
+    let mut std_map: HashMap<u32, String> = HashMap::new();
+    assert_eq!(replay(&trace[..], &mut std_map).unwrap(), 2001);
+
+    // an elastic map has to hash and place the values the same way
+    let mut other = ElasticHashMap::<u32, String>::with_capacity(8);
+    match replay::<u32, String, _>(&trace[..], &mut other) {
+        Err(ReplayError::Mismatch {
+            event, op, actual, ..
+        }) => assert_eq!((event, op, actual.as_str()), (0, "header", "seed null")),
+        result => panic!("{:?}", result),
+    }
+
+    // a map that disagrees is caught at the first wrong result
+    let text = String::from_utf8(trace).unwrap();
+    let first_get = text
+        .lines()
+        .position(|line| line.starts_with(r#"{"op":"get""#) && !line.contains(r#""result":null"#))
+        .unwrap();
+    let mut std_map = HashMap::new();
+    std_map.insert(u32::MAX, String::new());
+    match replay::<u32, String, _>(text.as_bytes(), &mut std_map) {
+        // the extra entry makes the length differ right away
+        Err(ReplayError::Mismatch { event, .. }) => assert_eq!(event, 0),
+        result => panic!("{:?}", result),
+    }
+    let mut lines: Vec<&str> = text.lines().collect();
+    let changed = lines[first_get].replace(r#""result":"v"#, r#""result":"w"#);
+    lines[first_get] = &changed;
+    let mut std_map = HashMap::new();
+    match replay::<u32, String, _>(lines.join("\n").as_bytes(), &mut std_map) {
+        Err(ReplayError::Mismatch { event, op, .. }) => {
+            assert_eq!((event, op), (first_get, "get"))
+        }
+        result => panic!("{:?}", result),
+    }
+    // a trace recorded with groups of another width lands elsewhere
+    let width = group::Group::WIDTH;
+    let other_width = text.replacen(
+        &format!("\"group_width\":{}", width),
+        &format!("\"group_width\":{}", 24 - width),
+        1,
+    );
+    let mut same: ElasticHashMap<u32, String, NoHashCache, SeededState> =
+        ElasticHashMap::with_hasher(SeededState::new(1));
+    match replay::<u32, String, _>(other_width.as_bytes(), &mut same) {
+        Err(ReplayError::Mismatch {
+            event, expected, ..
+        }) => assert_eq!(
+            (event, expected),
+            (0, format!("group_width {}", 24 - width))
+        ),
+        result => panic!("{:?}", result),
+    }
+    assert!(matches!(
+        replay::<u32, String, _>("{\"op\":\"get\"}".as_bytes(), &mut HashMap::new()),
+        Err(ReplayError::Parse { line: 1, .. })
+    ));
+}
+
+#[test]
+pub(crate) fn test_workload() {
+    use rand::Rng;
+    use std::{
+        collections::{HashMap, HashSet},
+        hash::BuildHasher,
+    };
+
+    let seed = test_rng().random();
+    let keys: Vec<u64> = Keys::uniform(seed, 1000).take(500).collect();
+    assert_eq!(
+        keys,
+        Keys::uniform(seed, 1000).take(500).collect::<Vec<_>>()
+    );
+    assert!(keys.iter().all(|&k| k < 1000));
+    assert!(Keys::sequential(7).take(5).eq(7..12));
+
+    // the hottest key of a Zipf(1) over 100 keys takes 1/H(100) ≈ 19% of the draws
+    let draws = 100000;
+    let mut counts = vec![0; 100];
+    for key in Keys::zipf(seed, 100, 1.0).take(draws) {
+        counts[key as usize] += 1;
+    }
+    let hottest = counts[0] as f64 / draws as f64;
+    assert!((hottest - 0.193).abs() < 0.02, "{}", hottest);
+    assert!(counts[0] > counts[9] && counts[9] > counts[99]);
+    assert!(Keys::zipf(seed, 10, 0.0).take(1000).all(|k| k < 10));
+
+    for key in StringKeys::new(seed, 3..=8).take(100) {
+        assert!((3..=8).contains(&key.len()));
+        assert!(key.bytes().all(|b| b.is_ascii_alphanumeric()));
+    }
+
+    let hasher = SeededState::new(seed);
+    let colliding: Vec<u64> = CollidingKeys::new(hasher, 64, 5, 0).take(50).collect();
+    assert!(colliding.windows(2).all(|w| w[0] < w[1]));
+    assert!(colliding.iter().all(|&k| hasher.hash_one(k) % 64 == 5));
+    // the probe sequences of the buckets do not follow `hash % bucket_len`
+    let mut map: ElasticHashMap<u64, u64, NoHashCache, SeededState> =
+        ElasticHashMap::with_hasher(hasher);
+    for &key in &colliding {
+        map.insert(key, key);
+    }
+    map.validate().unwrap();
+    assert!(colliding.iter().all(|k| map.get(k) == Some(k)));
+
+    // reads and removes of a mix target live keys, so a model of the
+    // operations tracks the map exactly
+    let mut ops = Operations::new(seed, OpMix::CHURN, Keys::uniform(seed, 500));
+    let mut map: ElasticHashMap<u64, u64> = ElasticHashMap::with_capacity(16);
+    let mut model = HashMap::new();
+    let (mut gets, mut removes) = (0, 0);
+    for op in ops.by_ref().take(20000) {
+        match op {
+            Op::Get(key) => {
+                gets += 1;
+                assert_eq!(map.get(&key), model.get(&key));
+            }
+            Op::Insert(key) => assert_eq!(map.insert(key, key), model.insert(key, key)),
+            Op::Remove(key) => {
+                removes += 1;
+                assert_eq!(map.remove(&key), Some(key));
+                model.remove(&key);
+            }
+        }
+    }
+    assert_eq!(ops.live(), map.len());
+    assert!((1500..2500).contains(&gets), "{}", gets);
+    assert!(removes > 8000, "{}", removes);
+
+    let reads = Operations::new(seed, OpMix::READ_HEAVY, Keys::sequential(0))
+        .take(10000)
+        .filter(|op| matches!(op, Op::Get(_)))
+        .count();
+    assert!((8700..9300).contains(&reads), "{}", reads);
+    let inserted: HashSet<_> = Operations::new(seed, OpMix::INSERT_ONLY, Keys::sequential(0))
+        .take(100)
+        .collect();
+    assert_eq!(inserted.len(), 100);
+}
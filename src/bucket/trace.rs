@@ -0,0 +1,144 @@
+//! Probe path of a single key, see `ElasticHashTable::explain`
+
+use std::fmt;
+
+use super::table::Slot;
+
+/// State of a slot, without its value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotKind {
+    Empty,
+    Occupied,
+    Tombstone,
+}
+
+impl<T> From<&Slot<T>> for SlotKind {
+    fn from(slot: &Slot<T>) -> Self {
+        match slot {
+            Slot::Empty => SlotKind::Empty,
+            Slot::Occupied(_) => SlotKind::Occupied,
+            Slot::Tombstone => SlotKind::Tombstone,
+        }
+    }
+}
+
+/// Why probing a bucket stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AbandonReason {
+    /// The group has an empty slot, so no value was placed further along
+    EmptySlot,
+    /// No insert ever probed the bucket deeper than this
+    ProbeDepth,
+    /// The insert gave up on the bucket after `f(ε)` attempts
+    ProbeLimit,
+}
+
+/// Part of the operation a step belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// The lookup, which walks all buckets in `phi` order
+    Lookup,
+    /// Probes of a hypothetical insert beyond what the lookup examined
+    Insert,
+}
+
+/// One `(i, j)` pair of the probe path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeStep {
+    /// 0-based bucket index, `i - 1`
+    pub bucket: usize,
+    /// Attempt `j` within the bucket
+    pub attempt: u32,
+    pub phase: Phase,
+    /// Absolute index of the first slot of the group examined, `None` when
+    /// the bucket was abandoned without examining one
+    pub group: Option<usize>,
+    /// Kind of every slot of the group that belongs to the bucket
+    pub slots: Vec<SlotKind>,
+    /// Set on the last step of a bucket
+    pub abandoned: Option<AbandonReason>,
+}
+
+/// Where the key is, or where inserting it would put it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceOutcome {
+    Found {
+        index: usize,
+        bucket: usize,
+        attempt: u32,
+    },
+    /// The key is absent and an insert would store it here
+    Insert {
+        index: usize,
+        bucket: usize,
+        attempt: u32,
+    },
+    /// The key is absent and the table would grow before inserting it
+    Grow,
+}
+
+/// Probe path of one key: every step of the lookup in `phi` order, then
+/// the probes a hypothetical insert would add
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeTrace {
+    pub hash: u64,
+    pub steps: Vec<ProbeStep>,
+    pub outcome: TraceOutcome,
+}
+
+impl fmt::Display for ProbeTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "hash {:#018x}: ", self.hash)?;
+        match self.outcome {
+            TraceOutcome::Found {
+                index,
+                bucket,
+                attempt,
+            } => writeln!(
+                f,
+                "found in slot {} of A_{} at j = {}",
+                index,
+                bucket + 1,
+                attempt
+            )?,
+            TraceOutcome::Insert {
+                index,
+                bucket,
+                attempt,
+            } => writeln!(
+                f,
+                "absent, would be inserted in slot {} of A_{} at j = {}",
+                index,
+                bucket + 1,
+                attempt
+            )?,
+            TraceOutcome::Grow => writeln!(f, "absent, the table would grow first")?,
+        }
+        for step in &self.steps {
+            write!(
+                f,
+                "  {:?} A_{} j = {}",
+                step.phase,
+                step.bucket + 1,
+                step.attempt
+            )?;
+            if let Some(group) = step.group {
+                let slots: String = step
+                    .slots
+                    .iter()
+                    .map(|kind| match kind {
+                        SlotKind::Empty => '.',
+                        SlotKind::Occupied => '#',
+                        SlotKind::Tombstone => 'x',
+                    })
+                    .collect();
+                write!(f, " slot {} [{}]", group, slots)?;
+            }
+            if let Some(reason) = step.abandoned {
+                write!(f, " abandoned: {:?}", reason)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
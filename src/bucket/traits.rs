@@ -1,43 +1,100 @@
-use std::hash::Hash;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+    hash::{BuildHasher, Hash},
+};
 
-use super::map::{ElasticHashMap, EntryState};
+use super::{
+    equivalent::Equivalent, hash_cache::HashCache, map::ElasticHashMap, observer::ElasticObserver,
+    table,
+};
 
 // 实现标准库的HashMap trait
-impl<K, V> std::ops::Index<K> for ElasticHashMap<K, V>
+impl<K, Q, V, H, S, O> std::ops::Index<&Q> for ElasticHashMap<K, V, H, S, O>
 where
     K: Eq + Hash + Clone,
+    Q: Hash + Equivalent<K> + ?Sized,
     V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+    S: BuildHasher,
 {
     type Output = V;
 
-    fn index(&self, key: K) -> &Self::Output {
-        self.get(&key).expect("no entry found for key")
+    fn index(&self, key: &Q) -> &Self::Output {
+        self.get(key).expect("no entry found for key")
     }
 }
 
-impl<K, V> std::ops::IndexMut<K> for ElasticHashMap<K, V>
+impl<K, Q, V, H, S, O> std::ops::IndexMut<&Q> for ElasticHashMap<K, V, H, S, O>
 where
     K: Eq + Hash + Clone,
+    Q: Hash + Equivalent<K> + ?Sized,
     V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+    S: BuildHasher,
 {
-    fn index_mut(&mut self, key: K) -> &mut Self::Output {
-        self.get_mut(&key).expect("no entry found for key")
+    fn index_mut(&mut self, key: &Q) -> &mut Self::Output {
+        self.get_mut(key).expect("no entry found for key")
     }
 }
 
+// Implement Debug trait, formatted like a map
+impl<K, V, H, S, O> fmt::Debug for ElasticHashMap<K, V, H, S, O>
+where
+    K: Eq + Hash + Clone + fmt::Debug,
+    V: Clone + fmt::Debug,
+    H: HashCache,
+    O: ElasticObserver,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Implement PartialEq and Eq traits
+impl<K, V, H, S, O> PartialEq for ElasticHashMap<K, V, H, S, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone + PartialEq,
+    H: HashCache,
+    O: ElasticObserver,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| other.get(key) == Some(value))
+    }
+}
+
+impl<K, V, H, S, O> Eq for ElasticHashMap<K, V, H, S, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone + Eq,
+    H: HashCache,
+    O: ElasticObserver,
+    S: BuildHasher,
+{
+}
+
 // 实现IntoIterator trait
-impl<K, V> IntoIterator for ElasticHashMap<K, V>
+impl<K, V, H, S, O> IntoIterator for ElasticHashMap<K, V, H, S, O>
 where
     K: Eq + Hash + Clone,
     V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
 {
     type Item = (K, V);
     type IntoIter = IntoIter<K, V>;
 
     fn into_iter(self) -> Self::IntoIter {
         IntoIter {
-            data: self.data,
-            index: 0,
+            inner: self.table.into_iter(),
         }
     }
 }
@@ -48,8 +105,7 @@ where
     K: Eq + Hash + Clone,
     V: Clone,
 {
-    data: Vec<EntryState<K, V>>,
-    index: usize,
+    inner: table::IntoIter<(K, V)>,
 }
 
 impl<K, V> Iterator for IntoIter<K, V>
@@ -60,24 +116,18 @@ where
     type Item = (K, V);
 
     fn next(&mut self) -> Option<Self::Item> {
-        while self.index < self.data.len() {
-            if let EntryState::Occupied((k, v)) =
-                std::mem::replace(&mut self.data[self.index], EntryState::Empty)
-            {
-                self.index += 1;
-                return Some((k, v));
-            }
-            self.index += 1;
-        }
-        None
+        self.inner.next()
     }
 }
 
 // 实现FromIterator trait
-impl<K, V> FromIterator<(K, V)> for ElasticHashMap<K, V>
+impl<K, V, H, S, O> FromIterator<(K, V)> for ElasticHashMap<K, V, H, S, O>
 where
     K: Eq + Hash + Clone,
     V: Clone,
+    H: HashCache,
+    S: BuildHasher + Default,
+    O: ElasticObserver + Default,
 {
     fn from_iter<T: IntoIterator<Item = (K, V)>>(iter: T) -> Self {
         let iter = iter.into_iter();
@@ -94,10 +144,13 @@ where
 }
 
 // Implement Default trait
-impl<K, V> Default for ElasticHashMap<K, V>
+impl<K, V, H, S, O> Default for ElasticHashMap<K, V, H, S, O>
 where
     K: Eq + Hash + Clone,
     V: Clone,
+    H: HashCache,
+    S: BuildHasher + Default,
+    O: ElasticObserver + Default,
 {
     fn default() -> Self {
         ElasticHashMap::with_capacity(16)
@@ -105,14 +158,80 @@ where
 }
 
 // Implement Extend trait
-impl<K, V> Extend<(K, V)> for ElasticHashMap<K, V>
+impl<K, V, H, S, O> Extend<(K, V)> for ElasticHashMap<K, V, H, S, O>
 where
     K: Eq + Hash + Clone,
     V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+    S: BuildHasher,
 {
     fn extend<T: IntoIterator<Item = (K, V)>>(&mut self, iter: T) {
-        for (k, v) in iter {
-            self.insert(k, v);
-        }
+        self.insert_batch(iter);
+    }
+}
+
+// Implement From traits for arrays and std maps
+impl<K, V, H, S, O, const N: usize> From<[(K, V); N]> for ElasticHashMap<K, V, H, S, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    S: BuildHasher + Default,
+    O: ElasticObserver + Default,
+{
+    fn from(pairs: [(K, V); N]) -> Self {
+        Self::from_iter(pairs)
+    }
+}
+
+impl<K, V, H, S, O, T> From<HashMap<K, V, T>> for ElasticHashMap<K, V, H, S, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    S: BuildHasher + Default,
+    O: ElasticObserver + Default,
+{
+    fn from(map: HashMap<K, V, T>) -> Self {
+        Self::from_iter(map)
+    }
+}
+
+impl<K, V, H, S, O> From<BTreeMap<K, V>> for ElasticHashMap<K, V, H, S, O>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    S: BuildHasher + Default,
+    O: ElasticObserver + Default,
+{
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Self::from_iter(map)
+    }
+}
+
+impl<K, V, H, S, O, T> From<ElasticHashMap<K, V, H, S, O>> for HashMap<K, V, T>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+    T: BuildHasher + Default,
+{
+    fn from(map: ElasticHashMap<K, V, H, S, O>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl<K, V, H, S, O> From<ElasticHashMap<K, V, H, S, O>> for BTreeMap<K, V>
+where
+    K: Eq + Hash + Clone + Ord,
+    V: Clone,
+    H: HashCache,
+    O: ElasticObserver,
+{
+    fn from(map: ElasticHashMap<K, V, H, S, O>) -> Self {
+        map.into_iter().collect()
     }
 }
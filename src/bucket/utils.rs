@@ -1,9 +1,63 @@
+use super::group::Group;
+
 /// Construct a valid delta
 ///
 /// delta is load factor of the hashtable, it's the fraction of
 /// free slots in the hashtable after it's considered full
 ///
 /// the 1/delta must be power of 2
-pub fn delta(x: i32) -> f32 {
-    1f32 / (1 << (x as usize)) as f32
+pub fn delta(x: i32) -> f64 {
+    1f64 / (1u64 << x) as f64
+}
+
+/// Smallest slot count whose elastic table holds `elements` elements at
+/// `delta`
+pub fn exact_slots_for(elements: usize, delta: f64) -> usize {
+    let reachable = |size: usize| (size as f64 * (1.0 - delta)) as usize;
+    let mut size = ((elements as f64 / (1.0 - delta)).ceil() as usize).max(1);
+    while reachable(size) < elements {
+        size += 1;
+    }
+    size
+}
+
+/// Smallest slot count whose elastic table holds `elements` elements at
+/// `delta`, rounded up to a whole number of groups once it takes more than
+/// one
+pub fn slots_for(elements: usize, delta: f64) -> usize {
+    let slots = exact_slots_for(elements, delta);
+    if slots <= Group::WIDTH {
+        slots
+    } else {
+        slots.next_multiple_of(Group::WIDTH)
+    }
+}
+
+/// Smallest power of two slot count whose elastic table holds `elements`
+/// elements at `delta`
+pub fn power_of_two_slots_for(elements: usize, delta: f64) -> usize {
+    exact_slots_for(elements, delta).next_power_of_two()
+}
+
+/// Hint the CPU to start loading the cache line holding `value`
+#[inline]
+pub fn prefetch<T>(value: &T) {
+    #[cfg(all(target_feature = "sse", target_arch = "x86"))]
+    use core::arch::x86;
+    #[cfg(all(target_feature = "sse", target_arch = "x86_64"))]
+    use core::arch::x86_64 as x86;
+
+    #[cfg(all(
+        target_feature = "sse",
+        any(target_arch = "x86", target_arch = "x86_64")
+    ))]
+    // SAFETY: prefetching is only a hint and never faults
+    unsafe {
+        x86::_mm_prefetch::<{ x86::_MM_HINT_T0 }>((value as *const T).cast());
+    }
+    #[cfg(not(all(
+        target_feature = "sse",
+        any(target_arch = "x86", target_arch = "x86_64")
+    )))]
+    let _ = value;
 }
@@ -0,0 +1,335 @@
+//! Reproducible key streams and operation mixes.
+//!
+//! Every generator is driven by a seed, so a benchmark, a fuzzer or a
+//! simulation run can be repeated key for key. Key streams are iterators:
+//! take as many keys as needed, hash them into an `ElasticSimulator`, or
+//! feed them to `Operations` to get a mix of reads, inserts and removes.
+
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, Hash},
+    ops::RangeInclusive,
+};
+
+use crate::probe::{splitmix64, SPLITMIX_GAMMA};
+
+/// SplitMix64 generator behind every stream of this module
+#[derive(Debug, Clone)]
+pub struct WorkloadRng {
+    state: u64,
+}
+
+impl WorkloadRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(SPLITMIX_GAMMA);
+        splitmix64(self.state)
+    }
+
+    /// Value in `[0, n)`, `n` must not be 0. The bias of the multiply-shift
+    /// reduction is below `n / 2^64`.
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((self.next_u64() as u128 * n as u128) >> 64) as u64
+    }
+
+    /// Value in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Distribution {
+    Uniform {
+        max: u64,
+    },
+    Sequential {
+        next: u64,
+    },
+    /// `cdf[k]` is the probability of drawing a rank of at most `k`
+    Zipf {
+        cdf: Vec<f64>,
+    },
+}
+
+/// Endless stream of integer keys, see the constructors
+#[derive(Debug, Clone)]
+pub struct Keys {
+    rng: WorkloadRng,
+    distribution: Distribution,
+}
+
+impl Keys {
+    /// Keys drawn uniformly from `[0, max)`, repeats included
+    pub fn uniform(seed: u64, max: u64) -> Self {
+        assert!(max > 0, "max must be greater than 0");
+        Self {
+            rng: WorkloadRng::new(seed),
+            distribution: Distribution::Uniform { max },
+        }
+    }
+
+    /// `start`, `start + 1`, … without repeats
+    pub fn sequential(start: u64) -> Self {
+        Self {
+            rng: WorkloadRng::new(0),
+            distribution: Distribution::Sequential { next: start },
+        }
+    }
+
+    /// Keys of `[0, n)` where key `k` is drawn with a probability
+    /// proportional to `1 / (k + 1)^skew`.
+    ///
+    /// A skew of 0 is uniform, around 1 a few hot keys take most of the
+    /// draws, as in the usual cache and key-value workloads. The cumulative
+    /// distribution is tabulated, which takes `n` floats of memory.
+    pub fn zipf(seed: u64, n: u64, skew: f64) -> Self {
+        assert!(n > 0, "n must be greater than 0");
+        assert!(skew >= 0.0, "skew must not be negative");
+        let mut cdf: Vec<f64> = (1..=n)
+            .scan(0.0, |sum, rank| {
+                *sum += (rank as f64).powf(-skew);
+                Some(*sum)
+            })
+            .collect();
+        let total = cdf[cdf.len() - 1];
+        cdf.iter_mut().for_each(|p| *p /= total);
+        Self {
+            rng: WorkloadRng::new(seed),
+            distribution: Distribution::Zipf { cdf },
+        }
+    }
+}
+
+impl Iterator for Keys {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        Some(match &mut self.distribution {
+            Distribution::Uniform { max } => self.rng.below(*max),
+            Distribution::Sequential { next } => {
+                let key = *next;
+                *next = next.wrapping_add(1);
+                key
+            }
+            Distribution::Zipf { cdf } => {
+                let p = self.rng.next_f64();
+                // rounding may leave the last entry just below 1
+                (cdf.partition_point(|&q| q <= p) as u64).min(cdf.len() as u64 - 1)
+            }
+        })
+    }
+}
+
+/// Endless stream of random alphanumeric strings
+#[derive(Debug, Clone)]
+pub struct StringKeys {
+    rng: WorkloadRng,
+    len: RangeInclusive<usize>,
+}
+
+impl StringKeys {
+    const ALPHABET: &'static [u8] =
+        b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+    /// Strings with a length drawn uniformly from `len`
+    pub fn new(seed: u64, len: RangeInclusive<usize>) -> Self {
+        assert!(!len.is_empty(), "the length range is empty");
+        Self {
+            rng: WorkloadRng::new(seed),
+            len,
+        }
+    }
+}
+
+impl Iterator for StringKeys {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let (min, max) = (*self.len.start(), *self.len.end());
+        let len = min + self.rng.below((max - min) as u64 + 1) as usize;
+        Some(
+            (0..len)
+                .map(|_| {
+                    Self::ALPHABET[self.rng.below(Self::ALPHABET.len() as u64) as usize] as char
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Integer keys whose hash under a given hasher is `residue` modulo
+/// `bucket_len`, in increasing order.
+///
+/// They all land in the same slot of a table that places a key at
+/// `hash % bucket_len`. The elastic table probes every bucket with its own
+/// sequence, seeded from the hash and the bucket index, so these keys
+/// should spread like any others there; a table that clusters them has
+/// lost that independence. Each key takes about `bucket_len` hashes to
+/// find.
+#[derive(Debug, Clone)]
+pub struct CollidingKeys<S> {
+    hash_builder: S,
+    bucket_len: u64,
+    residue: u64,
+    next: u64,
+}
+
+impl<S: BuildHasher> CollidingKeys<S> {
+    /// Search from key `start`, `residue` must be below `bucket_len`
+    pub fn new(hash_builder: S, bucket_len: usize, residue: usize, start: u64) -> Self {
+        assert!(
+            residue < bucket_len,
+            "residue {} is not below the bucket length {}",
+            residue,
+            bucket_len
+        );
+        Self {
+            hash_builder,
+            bucket_len: bucket_len as u64,
+            residue: residue as u64,
+            next: start,
+        }
+    }
+}
+
+impl<S: BuildHasher> Iterator for CollidingKeys<S> {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        loop {
+            let key = self.next;
+            self.next = self.next.checked_add(1)?;
+            if self.hash_builder.hash_one(key) % self.bucket_len == self.residue {
+                return Some(key);
+            }
+        }
+    }
+}
+
+/// One operation of a workload
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Op<K> {
+    Get(K),
+    Insert(K),
+    Remove(K),
+}
+
+/// Relative weights of the operations of a workload
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpMix {
+    pub reads: u32,
+    pub inserts: u32,
+    pub removes: u32,
+}
+
+impl OpMix {
+    /// 90% reads, 10% inserts
+    pub const READ_HEAVY: OpMix = OpMix::new(90, 10, 0);
+    /// As many inserts as reads
+    pub const BALANCED: OpMix = OpMix::new(50, 50, 0);
+    /// Inserts only, to fill a table
+    pub const INSERT_ONLY: OpMix = OpMix::new(0, 1, 0);
+    /// Removes keep up with inserts, so the size stays around its start and
+    /// tombstones pile up
+    pub const CHURN: OpMix = OpMix::new(10, 45, 45);
+
+    pub const fn new(reads: u32, inserts: u32, removes: u32) -> Self {
+        Self {
+            reads,
+            inserts,
+            removes,
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.reads as u64 + self.inserts as u64 + self.removes as u64
+    }
+}
+
+/// Endless stream of operations in the proportions of an `OpMix`.
+///
+/// Inserts take the next key of the key stream. Reads and removes target a
+/// key that is present at that point of the workload, as long as there is
+/// one; before that, reads take a fresh key from the stream and miss, and
+/// removes turn into inserts. The live keys are tracked, so the operations
+/// apply to any map that starts empty.
+#[derive(Debug, Clone)]
+pub struct Operations<I: Iterator> {
+    rng: WorkloadRng,
+    mix: OpMix,
+    keys: I,
+    live: Vec<I::Item>,
+    positions: HashMap<I::Item, usize>,
+}
+
+impl<I> Operations<I>
+where
+    I: Iterator,
+    I::Item: Clone + Eq + Hash,
+{
+    pub fn new(seed: u64, mix: OpMix, keys: I) -> Self {
+        assert!(mix.total() > 0, "the operation mix is empty");
+        Self {
+            rng: WorkloadRng::new(seed),
+            mix,
+            keys,
+            live: Vec::new(),
+            positions: HashMap::new(),
+        }
+    }
+
+    /// Draw the next operations in the proportions of `mix`, e.g. to fill
+    /// the map with `OpMix::INSERT_ONLY` before the measured mix
+    pub fn set_mix(&mut self, mix: OpMix) {
+        assert!(mix.total() > 0, "the operation mix is empty");
+        self.mix = mix;
+    }
+
+    /// Number of keys present after the operations so far
+    pub fn live(&self) -> usize {
+        self.live.len()
+    }
+
+    fn insert(&mut self) -> Option<Op<I::Item>> {
+        let key = self.keys.next()?;
+        if !self.positions.contains_key(&key) {
+            self.positions.insert(key.clone(), self.live.len());
+            self.live.push(key.clone());
+        }
+        Some(Op::Insert(key))
+    }
+}
+
+impl<I> Iterator for Operations<I>
+where
+    I: Iterator,
+    I::Item: Clone + Eq + Hash,
+{
+    type Item = Op<I::Item>;
+
+    fn next(&mut self) -> Option<Op<I::Item>> {
+        let pick = self.rng.below(self.mix.total());
+        let live = self.live.len() as u64;
+        if pick < self.mix.reads as u64 {
+            if live == 0 {
+                return self.keys.next().map(Op::Get);
+            }
+            let idx = self.rng.below(live) as usize;
+            Some(Op::Get(self.live[idx].clone()))
+        } else if pick < self.mix.reads as u64 + self.mix.inserts as u64 || live == 0 {
+            self.insert()
+        } else {
+            let idx = self.rng.below(live) as usize;
+            let key = self.live.swap_remove(idx);
+            self.positions.remove(&key);
+            if let Some(moved) = self.live.get(idx) {
+                self.positions.insert(moved.clone(), idx);
+            }
+            Some(Op::Remove(key))
+        }
+    }
+}
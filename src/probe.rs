@@ -1,6 +1,7 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
 
 /// Probe strategy enumeration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ProbeStrategy {
     Linear,
     Quadratic,
@@ -8,6 +9,117 @@ pub enum ProbeStrategy {
     Uniform, // Added uniform probing strategy
 }
 
+impl ProbeStrategy {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProbeStrategy::Linear => "linear",
+            ProbeStrategy::Quadratic => "quadratic",
+            ProbeStrategy::DoubleHash => "double-hash",
+            ProbeStrategy::Uniform => "uniform",
+        }
+    }
+
+    /// The strategy called `name`, see `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            ProbeStrategy::Linear,
+            ProbeStrategy::Quadratic,
+            ProbeStrategy::DoubleHash,
+            ProbeStrategy::Uniform,
+        ]
+        .into_iter()
+        .find(|strategy| strategy.name() == name)
+    }
+}
+
+/// Pseudo-random generator behind uniform probing.
+///
+/// The LCG stays the default, so existing seeds keep their probe sequences.
+/// The others mix small seeds such as sequential integer hashes better.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Prng {
+    /// 64-bit linear congruential generator, upper half of the state
+    #[default]
+    Lcg,
+    /// SplitMix64
+    SplitMix64,
+    /// PCG-XSH-RR with 64 bits of state
+    Pcg,
+    /// xorshift64*
+    XorShiftStar,
+}
+
+impl Prng {
+    pub fn name(self) -> &'static str {
+        match self {
+            Prng::Lcg => "lcg",
+            Prng::SplitMix64 => "splitmix64",
+            Prng::Pcg => "pcg",
+            Prng::XorShiftStar => "xorshift-star",
+        }
+    }
+
+    /// The generator called `name`, see `name`
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Prng::Lcg, Prng::SplitMix64, Prng::Pcg, Prng::XorShiftStar]
+            .into_iter()
+            .find(|prng| prng.name() == name)
+    }
+
+    /// Initial generator state for the seed `key`
+    fn seed(self, key: u64) -> u64 {
+        match self {
+            Prng::Lcg | Prng::SplitMix64 => key,
+            // the seeding routine of the reference implementation
+            Prng::Pcg => key
+                .wrapping_add(RANDOM_ADD)
+                .wrapping_mul(RANDOM_MUL)
+                .wrapping_add(RANDOM_ADD),
+            // xorshift spreads the few bits of a small seed slowly, and never
+            // leaves the all-zero state
+            Prng::XorShiftStar => match splitmix64(key) {
+                0 => SPLITMIX_GAMMA,
+                state => state,
+            },
+        }
+    }
+
+    /// Advance `state` and return the next 32 random bits
+    fn next(self, state: &mut u64) -> u32 {
+        match self {
+            Prng::Lcg => {
+                *state = state.wrapping_mul(RANDOM_MUL).wrapping_add(RANDOM_ADD);
+                (*state >> 32) as u32
+            }
+            Prng::SplitMix64 => {
+                *state = state.wrapping_add(SPLITMIX_GAMMA);
+                (splitmix64(*state) >> 32) as u32
+            }
+            Prng::Pcg => {
+                let old = *state;
+                *state = old.wrapping_mul(RANDOM_MUL).wrapping_add(RANDOM_ADD);
+                let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+                xorshifted.rotate_right((old >> 59) as u32)
+            }
+            Prng::XorShiftStar => {
+                let mut x = *state;
+                x ^= x >> 12;
+                x ^= x << 25;
+                x ^= x >> 27;
+                *state = x;
+                (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as u32
+            }
+        }
+    }
+}
+
+/// Output function of SplitMix64
+pub(crate) fn splitmix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
 /// Probe sequence generator
 pub struct ProbeSequence {
     initial_pos: usize,
@@ -17,11 +129,14 @@ pub struct ProbeSequence {
     secondary_hash: usize,
     // Random number generator state for uniform probing
     random_state: u64,
+    prng: Prng,
 }
 
 static PROBE_NUM: AtomicUsize = AtomicUsize::new(0);
 static RANDOM_MUL: u64 = 6364136223846793005;
 static RANDOM_ADD: u64 = 1442695040888963407;
+/// Increment of the SplitMix64 state, the golden ratio in fixed point
+pub(crate) static SPLITMIX_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
 
 pub fn reset_probe_num() {
     PROBE_NUM.store(0, Ordering::Relaxed);
@@ -34,12 +149,18 @@ pub fn get_probe_num() -> usize {
 impl ProbeSequence {
     /// Create a new probe sequence
     pub fn new(key: u64, capacity: usize, strategy: ProbeStrategy) -> Self {
+        Self::with_prng(key, capacity, strategy, Prng::default())
+    }
+
+    /// Create a new probe sequence, uniform probing draws from `prng`
+    pub fn with_prng(key: u64, capacity: usize, strategy: ProbeStrategy, prng: Prng) -> Self {
         let initial_pos = (key as usize) % capacity;
         // For double hashing, calculate the second hash value
         let secondary_hash = match strategy {
             ProbeStrategy::DoubleHash => {
-                // A simple second hash function, ensuring the result is not zero
-                1 + (key as usize % (capacity - 1))
+                // A simple second hash function, ensuring the result is odd so
+                // that it is coprime with power of two capacities
+                (1 + (key as usize % (capacity - 1).max(1))) | 1
             }
             _ => 0,
         };
@@ -50,30 +171,39 @@ impl ProbeSequence {
             capacity,
             strategy,
             secondary_hash,
-            random_state: key, // Use key as random seed
+            random_state: prng.seed(key), // Use key as random seed
+            prng,
         }
     }
 
     /// Get the next probe position
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> usize {
         self.next_no_limit() % self.capacity
     }
 
+    /// Position of probe `step` without advancing the sequence, for the
+    /// strategies with a closed form. `None` for uniform probing.
+    pub fn position_at(&self, step: usize) -> Option<usize> {
+        match self.strategy {
+            ProbeStrategy::Linear => Some(self.initial_pos + step),
+            ProbeStrategy::Quadratic => Some(self.initial_pos + (step + step * step) / 2),
+            ProbeStrategy::DoubleHash => Some(self.initial_pos + step * self.secondary_hash),
+            ProbeStrategy::Uniform => None,
+        }
+    }
+
     pub fn next_no_limit(&mut self) -> usize {
         PROBE_NUM.fetch_add(1, Ordering::Relaxed);
         let pos = match self.strategy {
             ProbeStrategy::Linear => self.initial_pos + self.current_step,
             ProbeStrategy::Quadratic => {
-                self.initial_pos + self.current_step + self.current_step * self.current_step
+                // triangular numbers, which visit every slot of a power of two capacity
+                self.initial_pos + (self.current_step + self.current_step * self.current_step) / 2
             }
             ProbeStrategy::DoubleHash => self.initial_pos + self.current_step * self.secondary_hash,
             ProbeStrategy::Uniform => {
-                // Use a simple linear congruential generator to generate pseudo-random sequence
-                self.random_state = self
-                    .random_state
-                    .wrapping_mul(RANDOM_MUL)
-                    .wrapping_add(RANDOM_ADD);
-                let random_increment = (self.random_state >> 32) as usize;
+                let random_increment = self.prng.next(&mut self.random_state) as usize;
                 self.initial_pos + random_increment
             }
         };